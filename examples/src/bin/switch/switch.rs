@@ -16,21 +16,23 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-fn create_switch_device(device_name: &str) -> Device {
+fn create_switch_device(device_name: &str) -> Result<Device> {
     let mut switch_dev = Device::new(device_name, DeviceType::Switch);
 
     let power_param = Param::new_power("Power", false);
 
-    switch_dev.add_param(power_param);
-    switch_dev.set_primary_param("Power");
+    switch_dev.add_param(power_param)?;
+    switch_dev.set_primary_param("Power")?;
 
-    switch_dev
+    Ok(switch_dev)
 }
 
 fn switch_cb(params: HashMap<String, Value>, device_handle: DeviceHandle) {
     log::info!("Received update: {:?}", params);
     log::info!("Reporting: {:?}", params);
-    device_handle.update_and_report(params);
+    if device_handle.update_and_report(params).is_err() {
+        log::error!("Failed to report params");
+    }
 }
 
 fn main() -> Result<()> {
@@ -45,9 +47,10 @@ fn main() -> Result<()> {
     node.set_info(rainmaker::node::Info {
         name: "Switch Example Node".to_string(),
         fw_version: "v1.0".to_string(),
-    });
+        ..Default::default()
+    })?;
 
-    let mut switch_device = create_switch_device("Switch");
+    let mut switch_device = create_switch_device("Switch")?;
     switch_device.register_callback(Box::new(switch_cb));
 
     // Declare it here since we want wifi to be connected after connect_wifi returns
@@ -56,7 +59,7 @@ fn main() -> Result<()> {
 
     log::info!("WiFi connected successfully");
 
-    node.add_device(switch_device);
+    node.add_device(switch_device)?;
 
     rmaker.register_node(node);
     rmaker.start()?;
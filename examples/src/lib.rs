@@ -25,6 +25,8 @@ pub fn connect_wifi(
     wifi_arc_mutex: Arc<Mutex<WifiMgr<'static>>>,
 ) -> Result<()> {
     let nvs_partition = NvsPartition::new("nvs")?;
+    // agent stores hash of published node configuration here, so that it is not republished on every restart
+    rmaker.set_runtime_partition(nvs_partition.clone())?;
 
     let prov_config = WifiProvBleConfig {
         service_name: String::from("PROV_SERVICE"),
@@ -51,8 +53,8 @@ pub fn connect_wifi(
     } else {
         log::info!("Node not provisioned. Starting WiFi provisioning.");
         rmaker.reg_user_mapping_ep(&mut prov_mgr);
-        prov_mgr.start()?;
-        prov_mgr.wait_for_provisioning();
+        rmaker.reg_wifi_scan_ep(&mut prov_mgr);
+        rmaker.provision(&mut prov_mgr, std::time::Duration::from_secs(30))?;
         std::thread::spawn(move || {
             std::thread::sleep(std::time::Duration::new(30, 0));
             log::info!("Stopping WiFi provisioning");
@@ -0,0 +1,301 @@
+//! Clock used by time-dependent paths of the agent, i.e. report throttling, heartbeat, periodic sampling,
+//! WiFi monitoring and MQTT reconnection.
+//!
+//! Defaults to the system clock. A [MockClock] can be installed using [set_clock] for advancing time
+//! deterministically in tests instead of waiting.
+//! ```rust
+//! let clock = Arc::new(MockClock::new());
+//! clock::set_clock(clock.clone())?;
+//! device.set_min_report_interval(Duration::from_secs(5));
+//! // reports coalesced by throttle are published right away
+//! clock.advance(Duration::from_secs(5));
+//! ```
+
+use std::{
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "mock-clock")]
+use crate::error::RmakerError;
+
+static CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+/// Source of time for the agent.
+pub trait Clock: Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+    /// Blocks the current thread for the provided duration.
+    fn sleep(&self, duration: Duration);
+    /// Blocks the current thread for the provided duration or till the signal is stopped.
+    ///
+    /// Returns false if the signal is stopped.
+    fn wait(&self, signal: &StopSignal, duration: Duration) -> bool;
+    /// Runs the task once the provided delay elapses, e.g. publishing reports coalesced by throttle.
+    ///
+    /// Defaults to running the task on a new thread after [thread::sleep].
+    fn run_after(&self, delay: Duration, task: Box<dyn FnOnce() + Send>) {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            task();
+        });
+    }
+}
+
+/// Signal for stopping threads which wait on the clock, e.g. periodic tasks.
+#[derive(Debug, Default)]
+pub struct StopSignal {
+    stopped: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the signal, waking threads waiting on it.
+    pub fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.changed.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+}
+
+/// Clock backed by [Instant] and [thread::sleep].
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+
+    fn wait(&self, signal: &StopSignal, duration: Duration) -> bool {
+        let stopped = signal.stopped.lock().unwrap();
+        let (stopped, _) = signal
+            .changed
+            .wait_timeout_while(stopped, duration, |stopped| !*stopped)
+            .unwrap();
+        !*stopped
+    }
+}
+
+/// Clock which advances only when [`advance`](MockClock::advance) is called.
+///
+/// Threads sleeping on the clock are woken once the time is advanced past their deadline. Tasks scheduled using
+/// [`run_after`](Clock::run_after) are run by [`advance`](MockClock::advance) itself, hence their effects can be
+/// asserted right after advancing.
+#[cfg(feature = "mock-clock")]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+    advanced: Condvar,
+    // (elapsed time at which task is due, task)
+    tasks: Mutex<Vec<(Duration, Box<dyn FnOnce() + Send>)>>,
+}
+
+#[cfg(feature = "mock-clock")]
+impl std::fmt::Debug for MockClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockClock")
+            .field("elapsed", &*self.elapsed.lock().unwrap())
+            .field("pending_tasks", &self.tasks.lock().unwrap().len())
+            .finish()
+    }
+}
+
+// stop signals do not notify the condvar of mock clock, hence are polled while waiting
+#[cfg(feature = "mock-clock")]
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(feature = "mock-clock")]
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+            advanced: Condvar::new(),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Moves the clock forward, waking threads whose sleep has elapsed and running tasks which are due, in order of
+    /// their deadlines, before returning.
+    pub fn advance(&self, duration: Duration) {
+        let due = {
+            let mut elapsed = self.elapsed.lock().unwrap();
+            *elapsed += duration;
+
+            let mut tasks = self.tasks.lock().unwrap();
+            let (mut due, pending): (Vec<_>, Vec<_>) = tasks
+                .drain(..)
+                .partition(|(deadline, _)| *deadline <= *elapsed);
+            *tasks = pending;
+            due.sort_by_key(|(deadline, _)| *deadline);
+            due
+        };
+        self.advanced.notify_all();
+
+        // locks are released since tasks may use the clock
+        for (_, task) in due {
+            task();
+        }
+    }
+}
+
+#[cfg(feature = "mock-clock")]
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "mock-clock")]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let elapsed = self.elapsed.lock().unwrap();
+        let deadline = *elapsed + duration;
+        let _elapsed = self
+            .advanced
+            .wait_while(elapsed, |elapsed| *elapsed < deadline)
+            .unwrap();
+    }
+
+    fn wait(&self, signal: &StopSignal, duration: Duration) -> bool {
+        let mut elapsed = self.elapsed.lock().unwrap();
+        let deadline = *elapsed + duration;
+        while *elapsed < deadline {
+            if signal.is_stopped() {
+                return false;
+            }
+            elapsed = self
+                .advanced
+                .wait_timeout(elapsed, STOP_POLL_INTERVAL)
+                .unwrap()
+                .0;
+        }
+
+        !signal.is_stopped()
+    }
+
+    fn run_after(&self, delay: Duration, task: Box<dyn FnOnce() + Send>) {
+        let deadline = *self.elapsed.lock().unwrap() + delay;
+        self.tasks.lock().unwrap().push((deadline, task));
+    }
+}
+
+/// Replaces the clock used by the agent. Should be called before the agent is initialized.
+///
+/// Returns an error if the clock is already in use.
+#[cfg(feature = "mock-clock")]
+pub fn set_clock(clock: Arc<dyn Clock>) -> Result<(), RmakerError> {
+    CLOCK
+        .set(clock)
+        .map_err(|_| RmakerError::AlreadyInitialized)
+}
+
+fn clock() -> &'static Arc<dyn Clock> {
+    CLOCK.get_or_init(|| Arc::new(SystemClock))
+}
+
+pub(crate) fn now() -> Instant {
+    clock().now()
+}
+
+pub(crate) fn sleep(duration: Duration) {
+    clock().sleep(duration)
+}
+
+// returns false once the signal is stopped
+pub(crate) fn wait(signal: &StopSignal, duration: Duration) -> bool {
+    clock().wait(signal, duration)
+}
+
+pub(crate) fn run_after(delay: Duration, task: impl FnOnce() + Send + 'static) {
+    clock().run_after(delay, Box::new(task))
+}
+
+// clock can be installed only once per process, hence shared by tests. tests advancing it hold the lock of mock
+// MQTT backend, see rmaker_mqtt::mock::lock
+#[cfg(all(test, feature = "mock-clock"))]
+pub(crate) fn mock() -> &'static Arc<MockClock> {
+    static MOCK: OnceLock<Arc<MockClock>> = OnceLock::new();
+    MOCK.get_or_init(|| {
+        let mock = Arc::new(MockClock::new());
+        set_clock(mock.clone()).expect("clock used before installing mock clock");
+        mock
+    })
+}
+
+#[cfg(all(test, feature = "mock-clock"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_run_once_due() {
+        let clock = MockClock::new();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        for (name, delay) in [("late", 5), ("early", 2)] {
+            let runs = runs.clone();
+            clock.run_after(
+                Duration::from_secs(delay),
+                Box::new(move || runs.lock().unwrap().push(name)),
+            );
+        }
+
+        clock.advance(Duration::from_secs(1));
+        assert!(runs.lock().unwrap().is_empty());
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(*runs.lock().unwrap(), vec!["early", "late"]);
+    }
+
+    #[test]
+    fn tasks_due_together_run_in_order_of_deadline() {
+        let clock = MockClock::new();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        for (name, delay) in [("third", 3), ("first", 1), ("second", 2)] {
+            let runs = runs.clone();
+            clock.run_after(
+                Duration::from_secs(delay),
+                Box::new(move || runs.lock().unwrap().push(name)),
+            );
+        }
+
+        clock.advance(Duration::from_secs(3));
+
+        assert_eq!(*runs.lock().unwrap(), vec!["first", "second", "third"]);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn scheduled_action_fires_without_waiting() {
+        let _mqtt = crate::rmaker_mqtt::mock::lock();
+        let fired = Arc::new(Mutex::new(false));
+        let fired_2 = fired.clone();
+        let started = Instant::now();
+        let mocked_start = mock().now();
+
+        run_after(Duration::from_secs(3600), move || {
+            *fired_2.lock().unwrap() = true
+        });
+        mock().advance(Duration::from_secs(3600));
+
+        assert!(*fired.lock().unwrap());
+        assert_eq!(now() - mocked_start, Duration::from_secs(3600));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,42 @@
+//! Module for observing commands received from RainMaker cloud.
+//!
+//! An interceptor can be set using [`set_command_interceptor`] to inspect every remote parameters message
+//! before it is dispatched to devices, e.g. for audit logging or access control.
+//!
+//! ```rust
+//! rmaker.set_command_interceptor(Box::new(|cmd: &RawCommand| {
+//!     log::info!("received command: {}", cmd.params);
+//!     CommandDecision::Proceed
+//! }));
+//! ```
+//!
+//! Commands carrying a request id can be acknowledged on `node/<node_id>/cmd_resp` with the applied values
+//! and a status, see [`set_command_ack`].
+//!
+//! [`set_command_interceptor`]: crate::Rainmaker::set_command_interceptor
+//! [`set_command_ack`]: crate::Rainmaker::set_command_ack
+
+use serde_json::Value;
+
+pub(crate) type CommandInterceptor =
+    Box<dyn Fn(&RawCommand) -> CommandDecision + Send + Sync + 'static>;
+
+/// Remote parameters message received from RainMaker cloud, before it is dispatched to devices.
+#[derive(Debug)]
+pub struct RawCommand {
+    /// Topic on which the message was received.
+    pub topic: String,
+    /// Received values in `{"<device>": {"<param>": <value>}}` format.
+    pub params: Value,
+    /// Id of request, if sent by the cloud under `request_id` key along with the values.
+    pub request_id: Option<String>,
+}
+
+/// Decision returned by command interceptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandDecision {
+    /// Dispatch the command to devices.
+    Proceed,
+    /// Discard the command.
+    Drop,
+}
@@ -1,5 +1,22 @@
 pub const USER_MAPPING_TOPIC_SUFFIX: &str = "user/mapping";
 pub const NODE_CONFIG_TOPIC_SUFFIX: &str = "config";
+pub const NODE_CONFIG_PATCH_TOPIC_SUFFIX: &str = "config/patch";
 pub const NODE_PARAMS_LOCAL_INIT_TOPIC_SUFFIX: &str = "params/local/init";
 pub const NODE_PARAMS_REMOTE_TOPIC_SUFFIX: &str = "params/remote";
 pub const NODE_PARAMS_LOCAL_TOPIC_SUFFIX: &str = "params/local";
+pub const NODE_PARAMS_GET_TOPIC_SUFFIX: &str = "params/get";
+pub const NODE_HEARTBEAT_TOPIC_SUFFIX: &str = "heartbeat";
+pub const NODE_ALERT_TOPIC_SUFFIX: &str = "alert";
+pub const NODE_CMD_RESP_TOPIC_SUFFIX: &str = "cmd_resp";
+pub const NODE_TS_DATA_TOPIC_SUFFIX: &str = "tsdata";
+// version of time series data format expected on tsdata topic
+pub const TS_DATA_VERSION: &str = "2021-09-13";
+// version of node configuration format
+pub const NODE_CONFIG_VERSION: &str = "2020-03-20";
+// key of request id in remote params, echoed back in command acknowledgment
+pub const COMMAND_REQUEST_ID_KEY: &str = "request_id";
+// public RainMaker broker, used unless overridden by MqttTlsConfig
+pub const MQTT_DEFAULT_HOST: &str = "a1p72mufdu6064-ats.iot.us-east-1.amazonaws.com";
+pub const MQTT_DEFAULT_PORT: u16 = 8883;
+// message size limit of AWS IoT, used by RainMaker
+pub const MQTT_MESSAGE_SIZE_LIMIT: usize = 128 * 1024;
@@ -12,8 +12,8 @@
 //! Parameter from [Param] module can be added as following:
 //! ```rust
 //! let power_param = Param::new_power(name:"Power", initial_value: false);
-//! device.add_param(power_param);
-//! device.set_primary_param(param_name: "Power");
+//! device.add_param(power_param)?;
+//! device.set_primary_param(param_name: "Power")?;
 //! ```
 //!
 //! A callback needed to be set for every device in order to report updated values of parameters.
@@ -22,7 +22,10 @@
 //! ```rust
 //! fn device_callback(params: HashMap<String, Value>, devcie_handle: DeviceHandle){
 //!     /* Write code for logging the received and reported values */
-//!     device_handle.update_and_report(params); // for reporting that params values were successfully updated
+//!     // for reporting that params values were successfully updated
+//!     if device_handle.update_and_report(params).is_err() {
+//!         log::error!("could not report params");
+//!     }
 //! }
 //! ```
 //!
@@ -34,17 +37,43 @@
 //! [Param]: crate::param::Param
 //! [register_callback]: crate::device::Device::register_callback
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use serde::Serialize;
 use serde_json::{json, Value};
 
-use crate::{factory, param::Param, rmaker_mqtt, NODE_PARAMS_LOCAL_TOPIC_SUFFIX};
-use crate::param::ParamValue;
+use crate::{
+    clock,
+    constants::TS_DATA_VERSION,
+    encoding,
+    error::{RmakerDeviceError, RmakerError, RmakerMqttError},
+    node::ParamObserver,
+    param::{Param, ParamTypes, ParamValue},
+    rmaker_mqtt, topics,
+    utils::{self, Attributes, WrappedInArcMutex},
+};
 
 pub(crate) type DeviceCbType =
     Box<dyn for<'a> Fn(HashMap<String, Value>, DeviceHandle<'a>) + Send + Sync + 'static>;
 
+/// Values received for a device, converted to the data types of respective parameters.
+pub type ParamUpdate = HashMap<String, ParamValue>;
+
+/// Delivery guarantee of parameter reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportMode {
+    /// Published with QoS 1 and queued while disconnected, for important state such as lock status.
+    #[default]
+    Confirmed,
+    /// Published with QoS 0 and dropped while disconnected or throttled, for cheap ephemeral telemetry.
+    FireAndForget,
+}
+
 #[derive(Serialize)]
 pub struct Device {
     name: String,
@@ -52,19 +81,58 @@ pub struct Device {
     device_type: DeviceType,
     #[serde(skip_serializing_if = "Option::is_none", rename = "primary")]
     primary_param: Option<String>,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
-    attributes: HashMap<String, String>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "utils::serialize_attributes"
+    )]
+    attributes: Attributes,
     params: Vec<Param>,
     #[serde(skip_serializing)]
     callback: Option<DeviceCbType>,
     #[serde(skip_serializing)]
-    local_params_topic: String,
+    publisher: Arc<ParamPublisher>,
 }
 
 pub struct DeviceHandle<'a> {
     pub params: &'a [Param],
     pub name: &'a str,
-    local_params_topic: &'a str,
+    publisher: &'a Arc<ParamPublisher>,
+}
+
+// reporting path shared by all handles of a device
+pub(crate) struct ParamPublisher {
+    device_name: String,
+    // set once the device is added to a node
+    node_id: Mutex<Option<String>>,
+    last_reported: Mutex<HashMap<String, Value>>,
+    throttle: Mutex<ReportThrottle>,
+}
+
+// coalesces reports published within min_interval of the previous publish
+#[derive(Default)]
+struct ReportThrottle {
+    min_interval: Option<Duration>,
+    last_publish: Option<Instant>,
+    pending: HashMap<String, Value>,
+    flush_scheduled: bool,
+}
+
+/// A cloneable handle for reporting parameter values of a device from outside of device callback.
+///
+/// Can be obtained using [Device::reporter] and moved to a different thread.
+///
+/// Example for reporting a sensor reading from a background thread:
+/// ```rust
+/// let reporter = device.reporter();
+/// std::thread::spawn(move || loop {
+///     reporter.report_param("Temperature", ParamValue::Float(read_temperature())).unwrap();
+///     std::thread::sleep(Duration::from_secs(10));
+/// });
+/// ```
+#[derive(Clone)]
+pub struct ParamReporter {
+    publisher: Arc<ParamPublisher>,
+    params: HashMap<String, WrappedInArcMutex<ParamValue>>,
 }
 
 impl Debug for Device {
@@ -82,10 +150,6 @@ impl Debug for Device {
 impl Device {
     /// This function creates an instance of device.
     pub fn new(name: &str, device_type: DeviceType) -> Self {
-        let mut buff = [0u8; 32];
-        let node_id = factory::get_node_id(&mut buff).unwrap();
-        let local_params_topic = format!("node/{}/{}", node_id, NODE_PARAMS_LOCAL_TOPIC_SUFFIX);
-
         Self {
             name: name.to_owned(),
             device_type,
@@ -93,24 +157,44 @@ impl Device {
             attributes: Default::default(),
             params: vec![],
             callback: None,
-            local_params_topic,
+            publisher: Arc::new(ParamPublisher {
+                device_name: name.to_owned(),
+                node_id: Mutex::new(None),
+                last_reported: Mutex::new(HashMap::new()),
+                throttle: Mutex::new(ReportThrottle::default()),
+            }),
         }
     }
 
     /// A parameter can be set as a primary parameter.
-    pub fn set_primary_param(&mut self, param_name: &str) {
-        self.primary_param = Some(param_name.to_string())
+    ///
+    /// Returns an error if the parameter is not associated with the device.
+    pub fn set_primary_param(&mut self, param_name: &str) -> Result<(), RmakerDeviceError> {
+        if self.param(param_name).is_none() {
+            return Err(RmakerDeviceError::ParamNotFound(param_name.to_string()));
+        }
+
+        self.primary_param = Some(param_name.to_string());
+        Ok(())
     }
 
+    /// Adds an attribute to the device. Attributes are reported in the order they are added.
+    ///
+    /// Value of an existing attribute with the same name is replaced.
     pub fn add_attribute(&mut self, name: String, value: String) {
-        self.attributes
-            .insert(name, value)
-            .expect("Failed to add attribute");
+        utils::set_attribute(&mut self.attributes, name, value);
     }
 
     /// This function associates a parameter with the device.
-    pub fn add_param(&mut self, param: Param) {
+    ///
+    /// Returns an error if a parameter with the same name is already associated with the device.
+    pub fn add_param(&mut self, param: Param) -> Result<(), RmakerDeviceError> {
+        if self.params.iter().any(|p| p.name() == param.name()) {
+            return Err(RmakerDeviceError::DuplicateParam(param.name().to_string()));
+        }
+
         self.params.push(param);
+        Ok(())
     }
 
     /// This function associates a callback that reports updates values of parameters.
@@ -118,30 +202,228 @@ impl Device {
         self.callback = Some(Box::new(cb));
     }
 
-    /// Function for assigning a name to device.
+    /// Same as [`register_callback`](Device::register_callback), but the callback receives values converted to
+    /// the data types of respective parameters.
+    ///
+    /// ```rust
+    /// device.register_typed_callback(|update, device_handle| {
+    ///     if let Some(ParamValue::Bool(power)) = update.get("Power") {
+    ///         log::info!("power: {}", power);
+    ///     }
+    ///     if device_handle.update_and_report_typed(update).is_err() {
+    ///         log::error!("could not report params");
+    ///     }
+    /// });
+    /// ```
+    pub fn register_typed_callback(
+        &mut self,
+        cb: impl for<'a> Fn(ParamUpdate, DeviceHandle<'a>) + Send + Sync + 'static,
+    ) {
+        self.callback = Some(Box::new(
+            move |params: HashMap<String, Value>, handle: DeviceHandle| {
+                // values are already validated against the params before invoking callback
+                let update = params
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        let param = handle.params.iter().find(|p| p.name() == name)?;
+                        Some((name.clone(), param.value_from_json(value)?))
+                    })
+                    .collect();
+                cb(update, handle);
+            },
+        ));
+    }
+
+    /// Removes the callback associated with the device.
+    ///
+    /// Values received for the device are ignored until a new callback is registered.
+    pub fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+
+    /// Returns the name identifying the device in node configuration and params.
+    ///
+    /// Does not change when the device is renamed from phone apps, see [`display_name`](Device::display_name).
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Adds a `Name` parameter initialized with the device name, so that users can rename the device from phone apps.
+    ///
+    /// Renames are applied and reported back by the agent, and node configuration is republished so that the new
+    /// name is retained across reconnects. A rename only changes [`display_name`](Device::display_name), the name
+    /// returned by [`name`](Device::name) and used as the device key in node configuration and params stays the same.
+    pub fn add_name_param(&mut self) -> Result<(), RmakerDeviceError> {
+        let param = Param::new_name("Name", &self.name);
+        self.add_param(param)
+    }
+
+    /// Returns the name of device shown in phone apps, i.e. value of the name parameter if added, else the device name.
+    pub fn display_name(&self) -> String {
+        let name_param = self
+            .params
+            .iter()
+            .find(|p| p.param_type() == &ParamTypes::Name);
+
+        match name_param.map(|p| p.value()) {
+            Some(ParamValue::String(name)) => name,
+            _ => self.name.clone(),
+        }
+    }
+
+    // applies accepted values to params and notifies the observer
+    fn apply_values(&self, values: &HashMap<String, ParamValue>, observer: Option<&ParamObserver>) {
+        for param in self.params.iter() {
+            if let Some(value) = values.get(param.name()) {
+                param.set_value(value.clone());
+                if let Some(observer) = observer {
+                    observer(&self.name, param.name(), value);
+                }
+            }
+        }
+    }
+
+    // converts a received value to the data type of param, e.g. 0/1 for bool params.
+    // returns None for unknown params, writes to read-only params and values of mismatched type
+    fn accept_value(&self, name: &str, value: &Value) -> Option<ParamValue> {
+        let param = match self.param(name) {
+            Some(param) => param,
+            None => {
+                log::error!("ignoring unknown param {}.{}", self.name, name);
+                return None;
+            }
+        };
+        if !param.is_writable() {
+            log::warn!("ignoring write to read-only param {}.{}", self.name, name);
+            return None;
+        }
+
+        let accepted = param.value_from_json(value);
+        if accepted.is_none() {
+            log::error!(
+                "ignoring invalid value {} for param {}.{}",
+                value,
+                self.name,
+                name
+            );
+        }
+        accepted
+    }
+
     /// This function associates a list of parameters to the device.
     pub fn params(&self) -> &[Param] {
         &self.params
     }
 
-    pub(crate) fn execute_callback(&self, params: HashMap<String, /* ParamDataType */ Value>) {
-        let cb = if self.callback.is_some() {
-            self.callback.as_ref().unwrap()
-        } else {
-            return;
-        };
+    /// Sets the minimum interval between two reports of the device.
+    ///
+    /// Reports made within the interval are coalesced and published once the interval elapses, carrying the latest values.
+    pub fn set_min_report_interval(&mut self, interval: Duration) {
+        self.publisher.throttle.lock().unwrap().min_interval = Some(interval);
+    }
 
-        let handle = DeviceHandle {
-            params: &self.params,
-            name: &self.name,
-            local_params_topic: &self.local_params_topic,
-        };
+    /// Returns a [ParamReporter] for reporting parameter values of the device from a different thread.
+    pub fn reporter(&self) -> ParamReporter {
+        ParamReporter {
+            publisher: self.publisher.clone(),
+            params: self
+                .params
+                .iter()
+                .map(|p| (p.name().to_string(), p.value_handle()))
+                .collect(),
+        }
+    }
+
+    /// Returns the parameter with provided name.
+    pub fn param(&self, name: &str) -> Option<&Param> {
+        self.params.iter().find(|p| p.name() == name)
+    }
+
+    // params are reported under the node the device is added to
+    pub(crate) fn set_node_id(&self, node_id: &str) {
+        *self.publisher.node_id.lock().unwrap() = Some(node_id.to_string());
+    }
+
+    // returns true if the device is renamed, in which case node configuration is to be republished
+    pub(crate) fn execute_callback(
+        &self,
+        params: HashMap<String, /* ParamDataType */ Value>,
+        observer: Option<&ParamObserver>,
+    ) -> bool {
+        let accepted: HashMap<String, ParamValue> = params
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let value = self.accept_value(&name, &value)?;
+                Some((name, value))
+            })
+            .collect();
+
+        // renames are handled by agent, phone apps expect the new name to be reported back
+        let (renames, updates): (HashMap<_, _>, HashMap<_, _>) =
+            accepted.into_iter().partition(|(name, _)| {
+                self.param(name)
+                    .is_some_and(|p| p.param_type() == &ParamTypes::Name)
+            });
+        let renamed = !renames.is_empty();
+        if renamed {
+            self.apply_values(&renames, observer);
+            if self
+                .publisher
+                .publish(to_json_values(&renames), false, ReportMode::Confirmed)
+                .is_err()
+            {
+                log::error!("could not report name of device {}", self.name);
+            }
+        }
+
+        if updates.is_empty() {
+            return renamed;
+        }
+
+        // apply received values so that params reflect the latest state, also when no callback is registered.
+        // values of weak params are applied once reported back by callback
+        let applied: HashMap<_, _> = updates
+            .iter()
+            .filter(|(name, _)| !self.param(name).is_some_and(|p| p.is_weak_update()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        self.apply_values(&applied, observer);
+
+        match self.callback.as_ref() {
+            Some(cb) => {
+                let handle = DeviceHandle {
+                    params: &self.params,
+                    name: &self.name,
+                    publisher: &self.publisher,
+                };
+                cb(to_json_values(&updates), handle);
+            }
+            None => log::debug!("no callback registered for device {}", self.name),
+        }
+
+        // momentary params snap back once the write is handled, cloud is informed of the reset
+        let reset: HashMap<String, ParamValue> = self
+            .params
+            .iter()
+            .filter(|p| p.is_momentary() && updates.contains_key(p.name()))
+            .map(|p| (p.name().to_string(), ParamValue::Bool(false)))
+            .collect();
+        if reset.is_empty() {
+            return renamed;
+        }
+        self.apply_values(&reset, None);
+        if self
+            .publisher
+            .publish(to_json_values(&reset), false, ReportMode::Confirmed)
+            .is_err()
+        {
+            log::error!(
+                "could not report reset of momentary params of {}",
+                self.name
+            );
+        }
 
-        cb(params, handle);
+        renamed
     }
 }
 
@@ -156,32 +438,280 @@ impl DeviceHandle<'_> {
     /// {
     ///     log::info!("Received update: {:?}", params);
     ///     log::info!("Reporting: {:?}", params);
-    ///     devcie_handle.update_and_report(params);
+    ///     if devcie_handle.update_and_report(params).is_err() {
+    ///         log::error!("could not report params");
+    ///     }
     /// }
     /// ```
-    pub fn update_and_report(&self, params: HashMap<String, Value>) {
-        let updated_params = json!({
-            self.name: params
+    ///
+    /// Returns an error if the values could not be published, e.g. when MQTT is not initialized or the device is not added to a node.
+    pub fn update_and_report(&self, params: HashMap<String, Value>) -> Result<(), RmakerMqttError> {
+        self.update_and_report_with_mode(params, ReportMode::Confirmed)
+    }
+
+    /// Same as [`update_and_report`](DeviceHandle::update_and_report), but with the provided delivery guarantee.
+    /// ```rust
+    /// device_handle.update_and_report_with_mode(readings, ReportMode::FireAndForget)?;
+    /// ```
+    pub fn update_and_report_with_mode(
+        &self,
+        mut params: HashMap<String, Value>,
+        mode: ReportMode,
+    ) -> Result<(), RmakerMqttError> {
+        apply_param_values(self.params, &params);
+        self.retain_reportable(&mut params);
+
+        self.publisher.publish(params, false, mode)
+    }
+
+    /// Same as [`update_and_report`](DeviceHandle::update_and_report), but takes typed values.
+    pub fn update_and_report_typed(&self, update: ParamUpdate) -> Result<(), RmakerMqttError> {
+        let params = update
+            .into_iter()
+            .map(|(name, value)| (name, Value::from(value)))
+            .collect();
+
+        self.update_and_report(params)
+    }
+
+    /// Same as [`update_and_report`](DeviceHandle::update_and_report), but reports only the parameters whose
+    /// values differ from the last reported values.
+    ///
+    /// Nothing is published if none of the values changed.
+    pub fn report_if_changed(
+        &self,
+        mut params: HashMap<String, Value>,
+    ) -> Result<(), RmakerMqttError> {
+        apply_param_values(self.params, &params);
+        self.retain_reportable(&mut params);
+
+        self.publisher.publish(params, true, ReportMode::Confirmed)
+    }
+
+    /// Reports parameter values sampled at the provided time, e.g. readings buffered while the device was asleep.
+    ///
+    /// Values are published as time series data, so that they are recorded at their actual sample time. RainMaker
+    /// cloud stamps values reported as params at receive time, params payload has no field for the sample time.
+    /// Current state of parameters is not modified.
+    pub fn report_with_timestamp(
+        &self,
+        mut params: HashMap<String, Value>,
+        timestamp: SystemTime,
+    ) -> Result<(), RmakerMqttError> {
+        self.retain_reportable(&mut params);
+        if params.is_empty() {
+            return Ok(());
+        }
+
+        self.publisher.send_time_series(&params, timestamp)
+    }
+
+    // momentary params are reported only by agent, once reset
+    fn retain_reportable(&self, params: &mut HashMap<String, Value>) {
+        params.retain(|name, _| {
+            !self
+                .params
+                .iter()
+                .any(|p| p.name() == name && p.is_momentary())
         });
+    }
+}
 
-        for param in self.params{
-            if let Some(value)  = params.get(param.name()){
-                let new_val = match param.value(){
-                    ParamValue::String(_) => ParamValue::String(value.to_string()),
-                    ParamValue::Bool(_) => ParamValue::Bool(value.as_bool().unwrap()),
-                    ParamValue::Integer(_) => ParamValue::Integer(value.as_i64().unwrap()),
-                    ParamValue::Float(_) => ParamValue::Float(value.as_f64().unwrap()),
-                };
+impl ParamReporter {
+    /// Updates the value of parameter and reports it to the RainMaker cloud over MQTT.
+    pub fn report_param(&self, name: &str, value: ParamValue) -> Result<(), RmakerError> {
+        let param_value = match self.params.get(name) {
+            Some(param_value) => param_value,
+            None => return Err(RmakerDeviceError::ParamNotFound(name.to_string()).into()),
+        };
+        *param_value.lock().unwrap() = value.clone();
 
-                param.set_value(new_val);
+        let params = HashMap::from([(name.to_string(), Value::from(value))]);
+        self.publisher
+            .publish(params, false, ReportMode::Confirmed)?;
+
+        Ok(())
+    }
+
+    /// Reports the value of parameter sampled at the provided time.
+    ///
+    /// Value is published as time series data, so that it is recorded at its actual sample time.
+    /// Current state of parameter is not modified.
+    pub fn report_param_with_timestamp(
+        &self,
+        name: &str,
+        value: ParamValue,
+        timestamp: SystemTime,
+    ) -> Result<(), RmakerError> {
+        if !self.params.contains_key(name) {
+            return Err(RmakerDeviceError::ParamNotFound(name.to_string()).into());
+        }
+
+        let params = HashMap::from([(name.to_string(), Value::from(value))]);
+        self.publisher.send_time_series(&params, timestamp)?;
+
+        Ok(())
+    }
+}
+
+impl ParamPublisher {
+    fn publish(
+        self: &Arc<Self>,
+        mut params: HashMap<String, Value>,
+        only_changed: bool,
+        mode: ReportMode,
+    ) -> Result<(), RmakerMqttError> {
+        if only_changed {
+            let last_reported = self.last_reported.lock().unwrap();
+            params.retain(|name, value| last_reported.get(name) != Some(value));
+        }
+
+        if params.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let mut throttle = self.throttle.lock().unwrap();
+            if let Some(min_interval) = throttle.min_interval {
+                // fire-and-forget reports are superseded by the next one, hence not coalesced
+                let throttled = throttle.flush_scheduled
+                    || throttle
+                        .last_publish
+                        .is_some_and(|last_publish| clock::now() - last_publish < min_interval);
+                if throttled && mode == ReportMode::FireAndForget {
+                    return Ok(());
+                }
+
+                if throttle.flush_scheduled {
+                    self.record_reported(&params);
+                    throttle.pending.extend(params);
+                    return Ok(());
+                }
+
+                let now = clock::now();
+                match throttle.last_publish {
+                    Some(last_publish) if now - last_publish < min_interval => {
+                        self.record_reported(&params);
+                        throttle.pending.extend(params);
+                        throttle.flush_scheduled = true;
+
+                        let publisher = self.clone();
+                        let wait = min_interval - (now - last_publish);
+                        clock::run_after(wait, move || publisher.flush());
+                        return Ok(());
+                    }
+                    _ => throttle.last_publish = Some(now),
+                }
             }
         }
 
-        rmaker_mqtt::publish(
-            self.local_params_topic,
-            updated_params.to_string().into_bytes(),
-        )
-        .unwrap();
+        self.send(&params, mode)?;
+        self.record_reported(&params);
+        Ok(())
+    }
+
+    // values are recorded once published or queued, so that dropped reports are not skipped as unchanged
+    fn record_reported(&self, params: &HashMap<String, Value>) {
+        let mut last_reported = self.last_reported.lock().unwrap();
+        for (name, value) in params {
+            last_reported.insert(name.clone(), value.clone());
+        }
+    }
+
+    // publishes reports coalesced by throttle
+    fn flush(&self) {
+        let pending = {
+            let mut throttle = self.throttle.lock().unwrap();
+            throttle.flush_scheduled = false;
+            throttle.last_publish = Some(clock::now());
+            std::mem::take(&mut throttle.pending)
+        };
+
+        if self.send(&pending, ReportMode::Confirmed).is_err() {
+            log::error!("could not report params of {}", self.device_name);
+        }
+    }
+
+    fn send(
+        &self,
+        params: &HashMap<String, Value>,
+        mode: ReportMode,
+    ) -> Result<(), RmakerMqttError> {
+        let updated_params = json!({
+            &self.device_name: params
+        });
+
+        let topic = self.topic(&topics::config().params_local)?;
+        let payload = encoding::encode(&updated_params);
+        match mode {
+            ReportMode::Confirmed => rmaker_mqtt::publish(&topic, payload),
+            ReportMode::FireAndForget => rmaker_mqtt::publish_fire_and_forget(&topic, payload),
+        }
+    }
+
+    fn topic(&self, suffix: &str) -> Result<String, RmakerMqttError> {
+        match self.node_id.lock().unwrap().as_deref() {
+            Some(node_id) => Ok(topics::node_topic(node_id, suffix)),
+            None => Err(RmakerMqttError::NotInitialized),
+        }
+    }
+
+    // params with timestamp are published as time series data, one record per param
+    fn send_time_series(
+        &self,
+        params: &HashMap<String, Value>,
+        timestamp: SystemTime,
+    ) -> Result<(), RmakerMqttError> {
+        let secs = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let ts_data: Vec<Value> = params
+            .iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": format!("{}.{}", self.device_name, name),
+                    "dt": ts_data_type(value),
+                    "ow": false,
+                    "records": [{"v": value, "t": secs}]
+                })
+            })
+            .collect();
+        let payload = json!({
+            "ts_data_version": TS_DATA_VERSION,
+            "ts_data": ts_data
+        });
+
+        let topic = self.topic(&topics::config().ts_data)?;
+        rmaker_mqtt::publish(&topic, encoding::encode(&payload))
+    }
+}
+
+// data type of value as named in time series data
+fn ts_data_type(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        _ => "object",
+    }
+}
+
+fn to_json_values(values: &HashMap<String, ParamValue>) -> HashMap<String, Value> {
+    values
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::from(value.clone())))
+        .collect()
+}
+
+fn apply_param_values(params: &[Param], values: &HashMap<String, Value>) {
+    for param in params {
+        if let Some(value) = values.get(param.name()) {
+            param.set_value_from_json(value);
+        }
     }
 }
 
@@ -233,7 +763,616 @@ pub enum DeviceType {
     #[serde(rename = "esp.device.security-panel")]
     SecurityPanel,
     #[serde(rename = "esp.device.water-heater")]
-    X,
+    WaterHeater,
+    #[serde(rename = "esp.device.sensor")]
+    Sensor,
+    #[serde(rename = "esp.device.gateway")]
+    Gateway,
     #[serde(rename = "esp.device.other")]
-    OTHER,
+    Other,
+    /// Custom device type. The provided string(e.g. `esp.device.custom-type`) is used as is.
+    #[serde(untagged)]
+    Custom(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::param::{ParamProperty, ParamUi};
+    use std::collections::HashSet;
+
+    #[cfg(feature = "mock-mqtt")]
+    const NODE_ID: &str = "node-1";
+
+    // params reported for the device since the last call
+    #[cfg(feature = "mock-mqtt")]
+    fn reported_params(device: &Device) -> Vec<Value> {
+        rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .filter(|msg| msg.topic == format!("node/{}/params/local", NODE_ID))
+            .filter_map(|msg| encoding::decode(&msg.payload))
+            .filter_map(|payload| payload.get(device.name()).cloned())
+            .collect()
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    fn handle(device: &Device) -> DeviceHandle<'_> {
+        DeviceHandle {
+            params: &device.params,
+            name: &device.name,
+            publisher: &device.publisher,
+        }
+    }
+
+    #[test]
+    fn custom_device_type_is_serialized_verbatim() {
+        let device_type = DeviceType::Custom("esp.device.air-purifier".to_string());
+
+        assert_eq!(
+            serde_json::to_value(device_type).unwrap(),
+            json!("esp.device.air-purifier")
+        );
+    }
+
+    #[test]
+    fn water_heater_is_serialized_as_standard_type() {
+        assert_eq!(
+            serde_json::to_value(DeviceType::WaterHeater).unwrap(),
+            json!("esp.device.water-heater")
+        );
+    }
+
+    #[test]
+    fn renamed_and_added_device_types_keep_wire_strings() {
+        let cases = [
+            (DeviceType::WaterHeater, "esp.device.water-heater"),
+            (DeviceType::Other, "esp.device.other"),
+            (DeviceType::Sensor, "esp.device.sensor"),
+            (DeviceType::Gateway, "esp.device.gateway"),
+        ];
+
+        for (device_type, expected) in cases {
+            assert_eq!(serde_json::to_value(device_type).unwrap(), json!(expected));
+        }
+    }
+
+    #[test]
+    fn add_param_rejects_duplicate_name() {
+        let mut device = Device::new("Switch", DeviceType::Switch);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+
+        let result = device.add_param(Param::new_toggle("Power", true));
+
+        assert!(matches!(result, Err(RmakerDeviceError::DuplicateParam(name)) if name == "Power"));
+        assert_eq!(device.params().len(), 1);
+    }
+
+    #[test]
+    fn primary_param_must_be_added_first() {
+        let mut device = Device::new("Switch", DeviceType::Switch);
+
+        let result = device.set_primary_param("Power");
+
+        assert!(matches!(result, Err(RmakerDeviceError::ParamNotFound(name)) if name == "Power"));
+        assert!(serde_json::to_value(&device)
+            .unwrap()
+            .get("primary")
+            .is_none());
+    }
+
+    #[test]
+    fn primary_param_is_serialized() {
+        let mut device = Device::new("Switch", DeviceType::Switch);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+
+        device.set_primary_param("Power").unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&device).unwrap()["primary"],
+            json!("Power")
+        );
+    }
+
+    #[test]
+    fn write_to_read_only_param_does_not_reach_callback() {
+        let mut device = Device::new("Room Sensor", DeviceType::TemperatureSensor);
+        device
+            .add_param(Param::new_temperature("Temperature", 20.0))
+            .unwrap();
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+        device.register_callback(Box::new(move |params, _handle: DeviceHandle| {
+            received_2.lock().unwrap().push(params);
+        }));
+
+        device.execute_callback(
+            HashMap::from([("Temperature".to_string(), json!(35.0))]),
+            None,
+        );
+        device.execute_callback(
+            HashMap::from([
+                ("Temperature".to_string(), json!(35.0)),
+                ("Power".to_string(), json!(true)),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![HashMap::from([("Power".to_string(), json!(true))])]
+        );
+        assert_eq!(
+            device.param("Temperature").unwrap().value(),
+            ParamValue::Float(20.0)
+        );
+    }
+
+    #[test]
+    fn received_values_are_applied_before_callback() {
+        let mut device = Device::new("Light", DeviceType::Lightbulb);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let seen = Arc::new(Mutex::new(None));
+        let seen_2 = seen.clone();
+        device.register_callback(Box::new(move |_params, handle: DeviceHandle| {
+            let power = handle.params.iter().find(|p| p.name() == "Power").unwrap();
+            *seen_2.lock().unwrap() = Some(power.value());
+        }));
+
+        device.execute_callback(HashMap::from([("Power".to_string(), json!(true))]), None);
+
+        assert_eq!(*seen.lock().unwrap(), Some(ParamValue::Bool(true)));
+        assert_eq!(
+            device.param("Power").unwrap().value(),
+            ParamValue::Bool(true)
+        );
+    }
+
+    #[test]
+    fn received_values_are_applied_without_callback() {
+        let mut device = Device::new("Fan", DeviceType::Fan);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device.register_callback(Box::new(|_params, _handle: DeviceHandle| {}));
+        device.clear_callback();
+
+        device.execute_callback(HashMap::from([("Power".to_string(), json!(true))]), None);
+
+        assert_eq!(
+            device.param("Power").unwrap().value(),
+            ParamValue::Bool(true)
+        );
+    }
+
+    // device keeps its name as key in node configuration and params, rename is reflected in display name
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn name_write_updates_display_name_and_is_reported() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Hall Light", DeviceType::Lightbulb);
+        device.set_node_id(NODE_ID);
+        device.add_name_param().unwrap();
+
+        let renamed = device.execute_callback(
+            HashMap::from([("Name".to_string(), json!("Porch Light"))]),
+            None,
+        );
+
+        assert!(renamed);
+        assert_eq!(device.display_name(), "Porch Light");
+        assert_eq!(device.name(), "Hall Light");
+        assert_eq!(
+            reported_params(&device),
+            vec![json!({"Name": "Porch Light"})]
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn trigger_is_reported_reset_after_write() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Doorbell", DeviceType::Other);
+        device.set_node_id(NODE_ID);
+        device.add_param(Param::new_trigger("Chime")).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+        device.register_callback(Box::new(move |params, _handle: DeviceHandle| {
+            received_2.lock().unwrap().push(params);
+        }));
+
+        device.execute_callback(HashMap::from([("Chime".to_string(), json!(true))]), None);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![HashMap::from([("Chime".to_string(), json!(true))])]
+        );
+        assert_eq!(
+            device.param("Chime").unwrap().value(),
+            ParamValue::Bool(false)
+        );
+        assert_eq!(reported_params(&device), vec![json!({"Chime": false})]);
+    }
+
+    #[test]
+    fn observer_is_notified_of_converted_values() {
+        let mut device = Device::new("Plug", DeviceType::Smartplug);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let observed = Mutex::new(Vec::new());
+        let observer = |device: &str, param: &str, value: &ParamValue| {
+            observed
+                .lock()
+                .unwrap()
+                .push((device.to_string(), param.to_string(), value.clone()));
+        };
+
+        device.execute_callback(
+            HashMap::from([("Power".to_string(), json!(1))]),
+            Some(&observer),
+        );
+
+        assert_eq!(
+            *observed.lock().unwrap(),
+            vec![(
+                "Plug".to_string(),
+                "Power".to_string(),
+                ParamValue::Bool(true)
+            )]
+        );
+    }
+
+    #[test]
+    fn received_integer_for_bool_param_is_normalized() {
+        let mut device = Device::new("Plug", DeviceType::Smartplug);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let received = Arc::new(Mutex::new(HashMap::new()));
+        let received_2 = received.clone();
+        device.register_callback(Box::new(move |params, _handle: DeviceHandle| {
+            *received_2.lock().unwrap() = params;
+        }));
+
+        device.execute_callback(HashMap::from([("Power".to_string(), json!(1))]), None);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            HashMap::from([("Power".to_string(), json!(true))])
+        );
+        assert_eq!(
+            device.param("Power").unwrap().value(),
+            ParamValue::Bool(true)
+        );
+    }
+
+    #[test]
+    fn typed_callback_receives_values_of_param_types() {
+        let mode = Param::new(
+            "Mode",
+            ParamValue::String("auto".to_string()),
+            ParamTypes::ModeController,
+            HashSet::from([ParamProperty::Read, ParamProperty::Write]),
+            ParamUi::Text,
+        );
+        let mut device = Device::new("Heater", DeviceType::Other);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device
+            .add_param(Param::new_slider("Level", 0, 10, 1, 0))
+            .unwrap();
+        device
+            .add_param(Param::new_float_slider("Target", 10.0, 30.0, 0.5, 20.0))
+            .unwrap();
+        device.add_param(mode).unwrap();
+        let received = Arc::new(Mutex::new(ParamUpdate::new()));
+        let received_2 = received.clone();
+        device.register_typed_callback(move |update, _handle| {
+            *received_2.lock().unwrap() = update;
+        });
+
+        // integral values are received for bool and float params
+        device.execute_callback(
+            HashMap::from([
+                ("Power".to_string(), json!(1)),
+                ("Level".to_string(), json!(7)),
+                ("Target".to_string(), json!(22)),
+                ("Mode".to_string(), json!("eco")),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            ParamUpdate::from([
+                ("Power".to_string(), ParamValue::Bool(true)),
+                ("Level".to_string(), ParamValue::Integer(7)),
+                ("Target".to_string(), ParamValue::Float(22.0)),
+                ("Mode".to_string(), ParamValue::String("eco".to_string())),
+            ])
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn reporter_reports_from_background_thread() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Room Sensor", DeviceType::TemperatureSensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_temperature("Temperature", 20.0))
+            .unwrap();
+        let reporter = device.reporter();
+
+        std::thread::spawn(move || reporter.report_param("Temperature", ParamValue::Float(23.5)))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            device.param("Temperature").unwrap().value(),
+            ParamValue::Float(23.5)
+        );
+        assert_eq!(reported_params(&device), vec![json!({"Temperature": 23.5})]);
+    }
+
+    #[test]
+    fn reporter_rejects_unknown_param() {
+        let device = Device::new("Room Sensor", DeviceType::TemperatureSensor);
+
+        let result = device
+            .reporter()
+            .report_param("Humidity", ParamValue::Float(40.0));
+
+        assert!(matches!(
+            result,
+            Err(RmakerError::Device(RmakerDeviceError::ParamNotFound(name))) if name == "Humidity"
+        ));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn report_before_adding_to_node_fails() {
+        let mut device = Device::new("Detached Switch", DeviceType::Switch);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let params = HashMap::from([("Power".to_string(), json!(true))]);
+
+        let result = handle(&device).update_and_report(params);
+
+        assert!(matches!(result, Err(RmakerMqttError::NotInitialized)));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn timestamp_is_published_only_when_provided() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Buffered Meter", DeviceType::Sensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_slider("Level", 0, 100, 1, 0))
+            .unwrap();
+        let level = |level: i64| HashMap::from([("Level".to_string(), json!(level))]);
+
+        handle(&device)
+            .report_with_timestamp(level(5), UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .unwrap();
+        handle(&device).update_and_report(level(6)).unwrap();
+
+        let published: Vec<(String, Value)> = rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .filter_map(|msg| Some((msg.topic, encoding::decode(&msg.payload)?)))
+            .collect();
+        assert_eq!(
+            published,
+            vec![
+                (
+                    format!("node/{}/tsdata", NODE_ID),
+                    json!({
+                        "ts_data_version": TS_DATA_VERSION,
+                        "ts_data": [{
+                            "name": "Buffered Meter.Level",
+                            "dt": "int",
+                            "ow": false,
+                            "records": [{"v": 5, "t": 1_700_000_000}]
+                        }]
+                    })
+                ),
+                (
+                    format!("node/{}/params/local", NODE_ID),
+                    json!({"Buffered Meter": {"Level": 6}})
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn report_if_changed_skips_unchanged_values() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Dedup Switch", DeviceType::Switch);
+        device.set_node_id(NODE_ID);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        let params = HashMap::from([("Power".to_string(), json!(true))]);
+
+        handle(&device).report_if_changed(params.clone()).unwrap();
+        handle(&device).report_if_changed(params).unwrap();
+
+        assert_eq!(reported_params(&device), vec![json!({"Power": true})]);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn report_if_changed_reports_only_changed_params() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Dedup Light", DeviceType::Lightbulb);
+        device.set_node_id(NODE_ID);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device
+            .add_param(Param::new_brightness("Brightness", 50))
+            .unwrap();
+
+        handle(&device)
+            .report_if_changed(HashMap::from([
+                ("Power".to_string(), json!(true)),
+                ("Brightness".to_string(), json!(50)),
+            ]))
+            .unwrap();
+        handle(&device)
+            .report_if_changed(HashMap::from([
+                ("Power".to_string(), json!(true)),
+                ("Brightness".to_string(), json!(80)),
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            reported_params(&device),
+            vec![
+                json!({"Power": true, "Brightness": 50}),
+                json!({"Brightness": 80})
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    #[test]
+    fn dropped_report_is_not_skipped_as_unchanged() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let mut device = Device::new("Lossy Meter", DeviceType::Sensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_slider("Level", 0, 100, 1, 0))
+            .unwrap();
+        device.set_min_report_interval(Duration::from_secs(5));
+        let level = |level: i64| HashMap::from([("Level".to_string(), json!(level))]);
+
+        handle(&device).update_and_report(level(1)).unwrap();
+        // dropped by throttle
+        handle(&device)
+            .update_and_report_with_mode(level(2), ReportMode::FireAndForget)
+            .unwrap();
+        clock.advance(Duration::from_secs(5));
+        handle(&device).report_if_changed(level(2)).unwrap();
+
+        assert_eq!(
+            reported_params(&device),
+            vec![json!({"Level": 1}), json!({"Level": 2})]
+        );
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    #[test]
+    fn reports_within_min_interval_are_coalesced() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let mut device = Device::new("Throttled Meter", DeviceType::Sensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_slider("Level", 0, 100, 1, 0))
+            .unwrap();
+        device.set_min_report_interval(Duration::from_secs(5));
+        let reporter = device.reporter();
+
+        for level in 1..=4 {
+            reporter
+                .report_param("Level", ParamValue::Integer(level))
+                .unwrap();
+        }
+        assert_eq!(reported_params(&device), vec![json!({"Level": 1})]);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(reported_params(&device), vec![json!({"Level": 4})]);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn report_modes_select_qos_and_queueing() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Mode Meter", DeviceType::Sensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_slider("Level", 0, 100, 1, 0))
+            .unwrap();
+        let level = |level: i64| HashMap::from([("Level".to_string(), json!(level))]);
+
+        handle(&device)
+            .update_and_report_with_mode(level(1), ReportMode::Confirmed)
+            .unwrap();
+        handle(&device)
+            .update_and_report_with_mode(level(2), ReportMode::FireAndForget)
+            .unwrap();
+        let qos: Vec<_> = rmaker_mqtt::mock::take_published()
+            .iter()
+            .map(|msg| msg.qos)
+            .collect();
+        assert_eq!(qos, [1, 0]);
+
+        // confirmed reports are queued while publishing is held, fire-and-forget reports are dropped
+        rmaker_mqtt::set_paused(true);
+        handle(&device)
+            .update_and_report_with_mode(level(3), ReportMode::Confirmed)
+            .unwrap();
+        handle(&device)
+            .update_and_report_with_mode(level(4), ReportMode::FireAndForget)
+            .unwrap();
+        assert!(rmaker_mqtt::mock::take_published().is_empty());
+
+        rmaker_mqtt::set_paused(false);
+        assert_eq!(reported_params(&device), vec![json!({"Level": 3})]);
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    #[test]
+    fn fire_and_forget_report_is_dropped_while_throttled() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let mut device = Device::new("Telemetry Meter", DeviceType::Sensor);
+        device.set_node_id(NODE_ID);
+        device
+            .add_param(Param::new_slider("Level", 0, 100, 1, 0))
+            .unwrap();
+        device.set_min_report_interval(Duration::from_secs(5));
+        let report = |level: i64| {
+            handle(&device)
+                .update_and_report_with_mode(
+                    HashMap::from([("Level".to_string(), json!(level))]),
+                    ReportMode::FireAndForget,
+                )
+                .unwrap()
+        };
+
+        report(1);
+        report(2);
+        assert_eq!(reported_params(&device), vec![json!({"Level": 1})]);
+
+        // dropped report is not coalesced into a later publish
+        clock.advance(Duration::from_secs(5));
+        assert!(reported_params(&device).is_empty());
+
+        report(3);
+        assert_eq!(reported_params(&device), vec![json!({"Level": 3})]);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn value_reported_by_callback_wins_over_weak_command() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut device = Device::new("Clamped Light", DeviceType::Lightbulb);
+        device.set_node_id(NODE_ID);
+        let mut level = Param::new_slider("Level", 0, 255, 1, 0);
+        level.set_weak_update(true);
+        device.add_param(level).unwrap();
+        let commanded = Arc::new(Mutex::new(Vec::new()));
+        let commanded_2 = commanded.clone();
+        device.register_callback(Box::new(move |params, handle: DeviceHandle| {
+            commanded_2.lock().unwrap().push(params["Level"].clone());
+            let clamped = params["Level"].as_i64().unwrap().min(100);
+            handle
+                .update_and_report(HashMap::from([("Level".to_string(), json!(clamped))]))
+                .unwrap();
+        }));
+
+        device.execute_callback(HashMap::from([("Level".to_string(), json!(150))]), None);
+
+        assert_eq!(*commanded.lock().unwrap(), [json!(150)]);
+        assert_eq!(reported_params(&device), vec![json!({"Level": 100})]);
+        assert_eq!(
+            device.param("Level").unwrap().value(),
+            ParamValue::Integer(100)
+        );
+    }
 }
@@ -0,0 +1,106 @@
+// encoding of node configuration and param payloads exchanged with cloud over MQTT.
+// local control and other payloads(heartbeat, alerts, user mapping) are always JSON
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+use crate::error::RmakerError;
+
+static ENCODING: OnceLock<PayloadEncoding> = OnceLock::new();
+
+/// Encoding of node configuration and param payloads published to and received from the cloud.
+///
+/// Defaults to JSON. CBOR requires the `cbor` feature and a backend which supports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    #[default]
+    Json,
+    /// Compact binary encoding, for bandwidth constrained links.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+// fails if any payload is encoded already
+pub(crate) fn set_encoding(encoding: PayloadEncoding) -> Result<(), RmakerError> {
+    ENCODING
+        .set(encoding)
+        .map_err(|_| RmakerError::AlreadyInitialized)
+}
+
+fn encoding() -> PayloadEncoding {
+    *ENCODING.get_or_init(PayloadEncoding::default)
+}
+
+pub(crate) fn encode(value: &Value) -> Vec<u8> {
+    encode_with(value, encoding())
+}
+
+// returns None if payload is not valid in the configured encoding
+pub(crate) fn decode(payload: &[u8]) -> Option<Value> {
+    decode_with(payload, encoding())
+}
+
+fn encode_with(value: &Value, encoding: PayloadEncoding) -> Vec<u8> {
+    match encoding {
+        PayloadEncoding::Json => value.to_string().into_bytes(),
+        #[cfg(feature = "cbor")]
+        PayloadEncoding::Cbor => {
+            let mut payload = Vec::new();
+            // writing to a vector does not fail and every JSON value is representable in CBOR
+            ciborium::into_writer(value, &mut payload).unwrap();
+            payload
+        }
+    }
+}
+
+fn decode_with(payload: &[u8], encoding: PayloadEncoding) -> Option<Value> {
+    match encoding {
+        PayloadEncoding::Json => serde_json::from_slice(payload).ok(),
+        #[cfg(feature = "cbor")]
+        PayloadEncoding::Cbor => ciborium::from_reader(payload).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn params() -> Value {
+        json!({
+            "Light": {"Power": true, "Brightness": 50, "Name": "Hall"},
+            "Thermostat": {"Setpoint": 22.5, "Modes": ["auto", "cool"]}
+        })
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let payload = encode_with(&params(), PayloadEncoding::Json);
+
+        assert_eq!(serde_json::from_slice::<Value>(&payload).unwrap(), params());
+        assert_eq!(decode_with(&payload, PayloadEncoding::Json), Some(params()));
+    }
+
+    #[test]
+    fn invalid_json_is_not_decoded() {
+        assert_eq!(decode_with(b"{\"Light\":", PayloadEncoding::Json), None);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let payload = encode_with(&params(), PayloadEncoding::Cbor);
+
+        assert_ne!(payload, encode_with(&params(), PayloadEncoding::Json));
+        assert_eq!(decode_with(&payload, PayloadEncoding::Cbor), Some(params()));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn json_payload_is_not_decoded_as_cbor() {
+        let payload = encode_with(&params(), PayloadEncoding::Json);
+
+        assert_ne!(decode_with(&payload, PayloadEncoding::Cbor), Some(params()));
+    }
+}
@@ -21,6 +21,30 @@ pub enum RmakerError {
     Mqtt(#[from] RmakerMqttError),
     #[error("factory partition error")]
     Factory(#[from] RmakerFactoryError),
+    #[error("runtime storage error")]
+    Storage(#[from] RmakerStorageError),
+    #[error("node configuration error")]
+    Node(#[from] RmakerNodeError),
+    #[error("device configuration error")]
+    Device(#[from] RmakerDeviceError),
+    #[error("node not registered")]
+    NodeNotRegistered,
+    #[error("claim data not found")]
+    ClaimDataMissing,
+    #[error("claim data is invalid")]
+    ClaimDataInvalid,
+    #[error("invalid topic {0}")]
+    InvalidTopic(String),
+    #[error("timed out")]
+    Timeout,
+    #[error("invalid message size warning fraction {0}, expected a value in (0, 1]")]
+    InvalidSizeWarningFraction(f64),
+    #[error("node configuration of {size} bytes exceeds limit of {limit} bytes")]
+    NodeConfigTooLarge { size: usize, limit: usize },
+    #[error("serialization error")]
+    Serialization(#[from] serde_json::Error),
+    #[error("not supported on this platform")]
+    Unsupported,
     #[error("other error")]
     UnknownError,
 }
@@ -35,4 +59,46 @@ pub enum RmakerFactoryError {
     PartitionNotFound,
     #[error("value read error")]
     ValueReadError,
+    #[error("value write error")]
+    ValueWriteError,
+}
+
+#[derive(Error, Debug)]
+pub enum RmakerStorageError {
+    #[error("already initialized")]
+    AlreadyInitialized,
+    #[error("runtime partition not set")]
+    NotInitialized,
+    #[error("namespace could not be opened")]
+    NamespaceNotFound,
+    #[error("value write error")]
+    ValueWriteError,
+}
+
+#[derive(Error, Debug)]
+pub enum RmakerNodeError {
+    #[error("node id is empty")]
+    EmptyNodeId,
+    #[error("node info not set")]
+    InfoNotSet,
+    #[error("firmware version is empty")]
+    EmptyFwVersion,
+    #[error("no devices added to node")]
+    NoDevices,
+    #[error("device with name {0} already exists")]
+    DuplicateDevice(String),
+    #[error("device with name {0} not found")]
+    DeviceNotFound(String),
+    #[error("value of param {0} can not be serialized")]
+    InvalidParamValue(String),
+}
+
+#[derive(Error, Debug)]
+pub enum RmakerDeviceError {
+    #[error("param with name {0} already exists")]
+    DuplicateParam(String),
+    #[error("param with name {0} not found")]
+    ParamNotFound(String),
+    #[error("value {0} can not be represented as a param value")]
+    UnsupportedValue(String),
 }
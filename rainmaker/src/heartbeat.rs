@@ -0,0 +1,115 @@
+// periodic application-level keepalive publish for the node
+use serde_json::json;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{self, StopSignal},
+    rmaker_mqtt, topics,
+};
+
+pub(crate) struct Heartbeat {
+    stop: Arc<StopSignal>,
+}
+
+// scheduled on the clock once per interval till heartbeat is dropped
+struct Beat {
+    node_id: String,
+    topic: String,
+    interval: Duration,
+    started_at: Instant,
+    stop: Arc<StopSignal>,
+}
+
+impl Heartbeat {
+    pub(crate) fn start(node_id: &str, interval: Duration) -> Self {
+        let stop = Arc::new(StopSignal::new());
+        Beat {
+            node_id: node_id.to_owned(),
+            topic: topics::node_topic(node_id, &topics::config().heartbeat),
+            interval,
+            started_at: clock::now(),
+            stop: stop.clone(),
+        }
+        .schedule();
+
+        Self { stop }
+    }
+}
+
+impl Beat {
+    fn schedule(self) {
+        clock::run_after(self.interval, move || {
+            if self.stop.is_stopped() {
+                return;
+            }
+
+            let payload = json!({
+                "node_id": self.node_id,
+                "uptime": (clock::now() - self.started_at).as_secs()
+            });
+            if rmaker_mqtt::publish(&self.topic, payload.to_string().into_bytes()).is_err() {
+                log::error!("could not publish heartbeat");
+            }
+            self.schedule();
+        });
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        // beat which is already scheduled is skipped
+        self.stop.stop();
+    }
+}
+
+#[cfg(all(test, feature = "mock-mqtt", feature = "mock-clock"))]
+mod tests {
+    use super::*;
+
+    fn heartbeats() -> Vec<serde_json::Value> {
+        rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .filter(|msg| msg.topic == "node/node-1/heartbeat")
+            .map(|msg| serde_json::from_slice(&msg.payload).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn heartbeat_is_published_at_interval() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let _heartbeat = Heartbeat::start("node-1", Duration::from_secs(30));
+
+        clock.advance(Duration::from_secs(29));
+        assert!(heartbeats().is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            heartbeats(),
+            vec![json!({"node_id": "node-1", "uptime": 30})]
+        );
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            heartbeats(),
+            vec![json!({"node_id": "node-1", "uptime": 60})]
+        );
+    }
+
+    #[test]
+    fn heartbeat_stops_once_dropped() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let heartbeat = Heartbeat::start("node-1", Duration::from_secs(30));
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(heartbeats().len(), 1);
+
+        drop(heartbeat);
+        clock.advance(Duration::from_secs(60));
+        assert!(heartbeats().is_empty());
+    }
+}
@@ -8,51 +8,156 @@
 //!
 //! Full fledged C based ESP RainMaker SDK can be found [here](https://github.com/espressif/esp-rainmaker).
 
+pub mod command;
 pub mod device;
 pub mod error;
 pub mod factory;
 pub mod node;
 pub mod param;
+pub mod sensor;
+pub mod wifi;
 
+pub(crate) mod encoding;
+pub(crate) mod heartbeat;
 pub(crate) mod local_ctrl;
 pub(crate) mod proto;
+pub(crate) mod storage;
+pub(crate) mod topics;
 pub(crate) mod utils;
+pub(crate) mod wifi_monitor;
 
+#[cfg(feature = "mock-clock")]
+pub mod clock;
+#[cfg(not(feature = "mock-clock"))]
+pub(crate) mod clock;
 mod constants;
 mod rmaker_mqtt;
 
-use constants::*;
-use error::RmakerError;
-use local_ctrl::RmakerLocalCtrl;
-use node::Node;
+use command::{CommandDecision, CommandInterceptor, RawCommand};
+use constants::{COMMAND_REQUEST_ID_KEY, MQTT_MESSAGE_SIZE_LIMIT, NODE_CONFIG_VERSION};
+use error::{RmakerError, RmakerStorageError};
+#[cfg(target_os = "linux")]
+use factory::ClaimData;
+use heartbeat::Heartbeat;
+use local_ctrl::{LocalCtrlProperty, RmakerLocalCtrl, LOCAL_CTRL_TYPE_CUSTOM};
+use node::{Node, SharedNode};
+use param::NumberHandling;
 use proto::esp_rmaker_user_mapping::*;
 use quick_protobuf::{MessageWrite, Writer};
+use wifi_monitor::WifiMonitor;
 // expose rainmaker_components crate for use in downstream crates
+pub use encoding::PayloadEncoding;
 pub use rainmaker_components as components;
 use rainmaker_components::{
     mqtt::ReceivedMessage,
+    persistent_storage::NvsPartition,
     wifi_prov::{WiFiProvTransportTrait, WifiProvMgr},
 };
+#[cfg(feature = "mock-mqtt")]
+pub use rmaker_mqtt::mock;
+pub use rmaker_mqtt::{MqttTlsConfig, ReconnectBackoff};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex, OnceLock},
-    thread,
+    sync::{Arc, Mutex, OnceLock, RwLock},
     time::Duration,
 };
+pub use topics::TopicConfig;
 
-#[cfg(target_os = "linux")]
-use rainmaker_components::persistent_storage::{Nvs, NvsPartition};
 #[cfg(target_os = "linux")]
 use std::{env, fs, path::Path};
 
 pub(crate) type WrappedInArcMutex<T> = Arc<Mutex<T>>;
 
+// republishes node configuration and param values if a device was renamed while handling received params, so that
+// cloud state survives reconnects. invoked once node is released, messages are queued while disconnected
+pub(crate) fn republish_changed_node_config(node: &SharedNode) {
+    if !node.read().unwrap().take_config_changed() {
+        return;
+    }
+
+    if let Some(rmaker) = unsafe { RAINMAKER.get() } {
+        if rmaker.update_node_config().is_err() {
+            log::error!("could not republish node configuration");
+        }
+    }
+}
+
+/// Snapshot of agent state for diagnostics, returned by [`debug_state`](Rainmaker::debug_state).
+#[derive(Debug, Clone)]
+pub struct DebugState {
+    pub node_id: String,
+    /// Names of devices of the registered node.
+    pub devices: Vec<String>,
+    /// MQTT topics subscribed by the agent, including custom ones, sorted.
+    pub subscribed_topics: Vec<String>,
+    pub connected: bool,
+    /// Size of the largest MQTT payload published so far, in bytes.
+    pub largest_payload: usize,
+}
+
+/// Events of provisioning steps handled by the agent, delivered to the callback set using
+/// [`set_prov_event_callback`](Rainmaker::set_prov_event_callback).
+///
+/// Steps of WiFi provisioning are reported when provisioning is run using [`provision`](Rainmaker::provision).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProvisioningEvent {
+    /// Provisioning is started, phone apps can connect to the node.
+    Started,
+    /// WiFi credentials are received from the phone app.
+    CredsReceived,
+    /// Node is connecting to the access point using received credentials.
+    Connecting,
+    /// Node is connected to the access point.
+    ConnectedToAp,
+    /// User-node mapping received from the phone app is published to RainMaker cloud.
+    CloudAssocDone,
+    /// A provisioning step failed, with the reason.
+    Failed(String),
+}
+
+/// Action taken when serialized node configuration exceeds the limit set using
+/// [`set_node_config_size_limit`](Rainmaker::set_node_config_size_limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSizePolicy {
+    /// Logs a warning and publishes the configuration anyway.
+    Warn,
+    /// Returns [`RmakerError::NodeConfigTooLarge`] without publishing.
+    Reject,
+}
+
+pub type ProvEventCb = Box<dyn Fn(ProvisioningEvent) + Send + Sync + 'static>;
+
+type ProvEndpointHandler = Box<dyn Fn(&str, &[u8]) -> Vec<u8> + Send + Sync + 'static>;
+
+// provisioning manager on which endpoints are registered, replaced in tests
+trait ProvEndpointRegistry {
+    fn add_endpoint(&mut self, name: &str, handler: ProvEndpointHandler);
+}
+
+impl<T: WiFiProvTransportTrait> ProvEndpointRegistry for WifiProvMgr<T> {
+    fn add_endpoint(&mut self, name: &str, handler: ProvEndpointHandler) {
+        WifiProvMgr::add_endpoint(self, name, handler)
+    }
+}
+
 /// A struct for RainMaker Agent.
 pub struct Rainmaker {
-    node: Option<Arc<node::Node>>,
+    node: Option<SharedNode>,
     node_id: String,
     local_ctrl: Option<RmakerLocalCtrl>,
+    heartbeat: Option<Heartbeat>,
+    command_interceptor: Arc<RwLock<Option<CommandInterceptor>>>,
+    prov_event_cb: Arc<RwLock<Option<ProvEventCb>>>,
+    retain_node_config: bool,
+    force_node_config: bool,
+    node_config_limit: (usize, ConfigSizePolicy),
+    // last node configuration known to cloud, base of partial updates
+    published_config: Mutex<Option<Value>>,
+    command_ack: bool,
+    wifi_monitor: Option<WifiMonitor>,
+    local_ctrl_properties: Vec<(String, LocalCtrlProperty)>,
+    local_ctrl_instance_name: Option<String>,
 }
 
 static mut RAINMAKER: OnceLock<Rainmaker> = OnceLock::new();
@@ -62,7 +167,7 @@ impl Rainmaker {
     ///
     /// Throws an error if agent is already initialized else returns the mutable reference of Rainmaker.
     ///
-    /// This function panics if node claiming is not performed.
+    /// Returns [`RmakerError::ClaimDataMissing`] or [`RmakerError::ClaimDataInvalid`] if node claiming is not performed.
     ///
     /// For claiming process, ensure following steps are performed:
     /// - Install [`esp-rainmaker-cli`](https://rainmaker.espressif.com/docs/cli-setup/) package.
@@ -80,25 +185,124 @@ impl Rainmaker {
     ///             ./rainmaker.py claim --mac <MAC addr> /dev/null
     ///         ```
     ///     3. Set the "RMAKER_CLAIMDATA_PATH" environment variable to the folder containing the Node X509 certificate and key (usually stored at ```/home/<user>/.espressif/rainmaker/claim_data/<acc_id>/<mac_addr>```)
+    ///
+    ///     With the `pkcs12` feature, certificate and key can instead be provided as a `node.p12` bundle in the folder,
+    ///     protected by the password in "RMAKER_CLAIMDATA_P12_PASSWORD" environment variable.
+    ///
+    /// On Linux, a claim profile can be selected using the "RMAKER_PROFILE" environment variable. See [`init_with_profile`](Rainmaker::init_with_profile).
     pub fn init() -> Result<&'static mut Self, RmakerError> {
         #[cfg(target_os = "linux")]
-        Self::linux_init_claimdata();
+        match env::var("RMAKER_PROFILE") {
+            Ok(profile) if !profile.is_empty() => return Self::init_with_profile(&profile),
+            _ => Self::linux_init_claimdata(None)?,
+        }
+
+        Self::init_agent()
+    }
+
+    /// Returns the initialized agent, or `None` if the agent is not initialized yet.
+    ///
+    /// Useful for modules which can not be handed the instance returned by [`init`](Rainmaker::init).
+    /// ```rust
+    /// // SAFETY: no other reference to the agent is in use
+    /// if let Some(rmaker) = unsafe { Rainmaker::get() } {
+    ///     rmaker.raise_alert("low battery")?;
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Returned reference aliases the one returned by [`init`](Rainmaker::init) and by every other call of this
+    /// function. Caller must ensure that no other reference to the agent is used while the returned one is alive,
+    /// e.g. by accessing the agent from a single thread only.
+    pub unsafe fn get() -> Option<&'static mut Self> {
+        unsafe { RAINMAKER.get_mut() }
+    }
+
+    /// Returns the initialized agent, initializing it using [`init`](Rainmaker::init) if not done already.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`get`](Rainmaker::get).
+    pub unsafe fn get_or_init() -> Result<&'static mut Self, RmakerError> {
+        match unsafe { Self::get() } {
+            Some(rmaker) => Ok(rmaker),
+            None => Self::init(),
+        }
+    }
+
+    /// Initializes the RainMaker Agent using credentials of the named claim profile, e.g. for switching between
+    /// staging and production accounts.
+    ///
+    /// Credentials of each profile are stored in a separate factory namespace(`<namespace>_<profile>`).
+    /// When running for the first time with a profile, claim data is read from the folder pointed by
+    /// "RMAKER_CLAIMDATA_PATH_<PROFILE>" environment variable, where profile name is in uppercase.
+    /// ```bash
+    ///     RMAKER_PROFILE=staging RMAKER_CLAIMDATA_PATH_STAGING=<claimdata folder> cargo run_linux <example>
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn init_with_profile(profile: &str) -> Result<&'static mut Self, RmakerError> {
+        if unsafe { RAINMAKER.get().is_some() } {
+            return Err(RmakerError::AlreadyInitialized);
+        }
+
+        factory::set_profile(profile)?;
+        Self::linux_init_claimdata(Some(profile))?;
+
+        Self::init_agent()
+    }
+
+    /// Initializes the RainMaker Agent using node credentials provided in memory.
+    ///
+    /// Credentials are stored in the factory namespace instead of being read from `RMAKER_CLAIMDATA_PATH`.
+    /// Useful when credentials are injected through environment or secret managers.
+    /// ```rust
+    /// let rmaker = Rainmaker::init_with_claim(ClaimData {
+    ///     node_id,
+    ///     cert,
+    ///     key,
+    ///     random,
+    /// })?;
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn init_with_claim(claim_data: ClaimData) -> Result<&'static mut Self, RmakerError> {
+        if unsafe { RAINMAKER.get().is_some() } {
+            return Err(RmakerError::AlreadyInitialized);
+        }
+
+        factory::set_claim_data(&claim_data)?;
+
+        Self::init_agent()
+    }
+
+    /// Initializes the RainMaker Agent in offline mode with the provided Node ID.
+    ///
+    /// Claim data is not required and no connection to RainMaker cloud is made. Published messages are logged
+    /// instead, and cloud commands can be simulated using [`simulate_remote_params`](Rainmaker::simulate_remote_params).
+    /// Useful for iterating on device logic without network.
+    pub fn init_offline(node_id: &str) -> Result<&'static mut Self, RmakerError> {
+        if unsafe { RAINMAKER.get().is_some() } {
+            return Err(RmakerError::AlreadyInitialized);
+        }
 
+        rmaker_mqtt::init_offline()?;
+
+        Self::set_agent(node_id.to_string())
+    }
+
+    fn init_agent() -> Result<&'static mut Self, RmakerError> {
         if unsafe { RAINMAKER.get().is_some() } {
             return Err(RmakerError::AlreadyInitialized);
         }
 
+        let node_id = factory::get_node_id()?;
+
+        Self::set_agent(node_id)
+    }
+
+    fn set_agent(node_id: String) -> Result<&'static mut Self, RmakerError> {
         unsafe {
-            let mut buff = [0u8; 32];
-            let node_id = factory::get_node_id(&mut buff)?;
-            if RAINMAKER
-                .set(Self {
-                    node: None,
-                    node_id,
-                    local_ctrl: None,
-                })
-                .is_err()
-            {
+            if RAINMAKER.set(Self::new_agent(node_id)).is_err() {
                 log::error!("Failed to initialize RainMaker");
                 return Err(RmakerError::UnknownError);
             };
@@ -106,6 +310,25 @@ impl Rainmaker {
         Ok(unsafe { RAINMAKER.get_mut().unwrap() })
     }
 
+    fn new_agent(node_id: String) -> Self {
+        Self {
+            node: None,
+            node_id,
+            local_ctrl: None,
+            heartbeat: None,
+            command_interceptor: Arc::new(RwLock::new(None)),
+            prov_event_cb: Arc::new(RwLock::new(None)),
+            retain_node_config: false,
+            force_node_config: false,
+            node_config_limit: (MQTT_MESSAGE_SIZE_LIMIT, ConfigSizePolicy::Warn),
+            published_config: Mutex::new(None),
+            command_ack: false,
+            wifi_monitor: None,
+            local_ctrl_properties: Vec::new(),
+            local_ctrl_instance_name: None,
+        }
+    }
+
     /// Returns Node ID.
     pub fn get_node_id(&self) -> &str {
         &self.node_id
@@ -116,184 +339,2252 @@ impl Rainmaker {
     /// Reports node configuration and initial values of parameters, subscribe to respective topics and wait for commands.
     /// # Ensure agent(node) is initialized and WiFi is connected before using this function.
     pub fn start(&mut self) -> Result<(), RmakerError> {
-        // initialize mqtt if not done already
-        if !rmaker_mqtt::is_mqtt_initialized() {
-            rmaker_mqtt::init_rmaker_mqtt()?;
-        }
-
-        let curr_node = &self.node;
-        let node_id = self.get_node_id();
-        let node_config_topic = format!("node/{}/{}", node_id, NODE_CONFIG_TOPIC_SUFFIX);
-        let params_local_init_topic =
-            format!("node/{}/{}", node_id, NODE_PARAMS_LOCAL_INIT_TOPIC_SUFFIX);
-        let remote_param_topic = format!("node/{}/{}", node_id, NODE_PARAMS_REMOTE_TOPIC_SUFFIX);
-
-        match curr_node {
-            Some(node) => {
-                let node_config = serde_json::to_string(node.as_ref()).unwrap();
-                log::info!("publishing nodeconfig: {}", node_config);
-                rmaker_mqtt::publish(&node_config_topic, node_config.into())?;
-
-                let init_params = node.get_param_values();
-                let init_params = serde_json::to_string(&init_params).unwrap();
-                log::info!("publishing initial params: {}", init_params);
-                rmaker_mqtt::publish(&params_local_init_topic, init_params.into())?;
-                let node = node.clone();
-                let node_2 = node.clone();
-                thread::sleep(Duration::from_secs(1)); // wait for connection
-                rmaker_mqtt::subscribe(&remote_param_topic, move |msg| {
-                    remote_params_callback(msg, &node)
-                })?;
-
-                let local_ctrl = RmakerLocalCtrl::new(node_2, node_id);
-
-                self.local_ctrl = if let Ok(local_ctrl) = local_ctrl {
-                    Some(local_ctrl)
-                } else {
-                    None
-                };
-            }
+        connect_mqtt()?;
+
+        let node = self.registered_node();
+        let node_id = self.node_id.clone();
+
+        let node_config = initial_messages(
+            &node.read().unwrap(),
+            &node_id,
+            self.retain_node_config,
+            self.force_node_config,
+            self.node_config_limit,
+        )?
+        .publish()?;
+        self.set_published_config(node_config);
+
+        rmaker_mqtt::subscribe_many(self.node_subscriptions(&node))?;
+
+        set_reconnect_reporting(node.clone(), node_id);
+        self.init_local_ctrl(node);
+
+        Ok(())
+    }
+
+    /// Async variant of [`start`](Rainmaker::start) for applications running on tokio runtime.
+    ///
+    /// Blocking MQTT operations are performed on the blocking thread pool of the runtime.
+    #[cfg(feature = "async")]
+    pub async fn start_async(&mut self) -> Result<(), RmakerError> {
+        tokio::task::spawn_blocking(connect_mqtt)
+            .await
+            .map_err(|_| RmakerError::UnknownError)??;
+
+        let node = self.registered_node();
+        let node_id = self.node_id.clone();
+
+        let messages = initial_messages(
+            &node.read().unwrap(),
+            &node_id,
+            self.retain_node_config,
+            self.force_node_config,
+            self.node_config_limit,
+        )?;
+        let node_config = tokio::task::spawn_blocking(move || messages.publish())
+            .await
+            .map_err(|_| RmakerError::UnknownError)??;
+        self.set_published_config(node_config);
+
+        rmaker_mqtt::subscribe_many_async(self.node_subscriptions(&node)).await?;
+
+        set_reconnect_reporting(node.clone(), node_id);
+        self.init_local_ctrl(node);
+
+        Ok(())
+    }
+
+    // topics on which node receives commands from cloud
+    fn node_subscriptions(
+        &self,
+        node: &SharedNode,
+    ) -> Vec<(String, Box<dyn rmaker_mqtt::TopicCb>)> {
+        let remote_param_topic = topics::node_topic(&self.node_id, &topics::config().params_remote);
+        let node_2 = node.clone();
+        let interceptor = self.command_interceptor.clone();
+        let ack_topic = self
+            .command_ack
+            .then(|| topics::node_topic(&self.node_id, &topics::config().cmd_resp));
+        let remote_params_cb = move |msg: ReceivedMessage| {
+            remote_params_callback(msg, &node_2, &interceptor, ack_topic.as_deref())
+        };
+
+        let params_get_topic = topics::node_topic(&self.node_id, &topics::config().params_get);
+        let node_3 = node.clone();
+        let params_local_topic = topics::node_topic(&self.node_id, &topics::config().params_local);
+        let params_get_cb = rmaker_mqtt::with_reply(move |_msg: ReceivedMessage| {
+            Some((params_local_topic.clone(), current_params(&node_3)?))
+        });
+
+        vec![
+            (
+                remote_param_topic,
+                Box::new(remote_params_cb) as Box<dyn rmaker_mqtt::TopicCb>,
+            ),
+            (params_get_topic, Box::new(params_get_cb)),
+        ]
+    }
+
+    fn registered_node(&self) -> SharedNode {
+        match &self.node {
+            Some(node) => node.clone(),
             None => panic!("error while starting: node not registered"),
         }
+    }
+
+    fn init_local_ctrl(&mut self, node: SharedNode) {
+        // local control requires network
+        if rmaker_mqtt::is_local() {
+            return;
+        }
+
+        let local_ctrl = RmakerLocalCtrl::new(
+            node,
+            &self.node_id,
+            self.local_ctrl_instance_name.as_deref(),
+            &self.local_ctrl_properties,
+        );
+
+        self.local_ctrl = if let Ok(local_ctrl) = local_ctrl {
+            Some(local_ctrl)
+        } else {
+            None
+        };
+    }
+
+    /// Starts local control again after it is stopped using [`stop_local_ctrl`](Rainmaker::stop_local_ctrl).
+    ///
+    /// Local control is started along with the agent, hence this is not required otherwise.
+    /// Returns an error if node is not registered.
+    pub fn start_local_ctrl(&mut self) -> Result<(), RmakerError> {
+        if self.local_ctrl.is_some() {
+            return Ok(());
+        }
+
+        let node = match &self.node {
+            Some(node) => node.clone(),
+            None => return Err(RmakerError::NodeNotRegistered),
+        };
+        self.init_local_ctrl(node);
 
         Ok(())
     }
 
-    /// Registers node to agent.
+    /// Stops local control and its mDNS advertisement while the agent keeps running, e.g. on untrusted networks.
+    pub fn stop_local_ctrl(&mut self) {
+        self.local_ctrl = None;
+    }
+
+    /// Sets the mDNS instance name under which local control service is advertised. Defaults to Node ID.
+    ///
+    /// On Linux, a numeric suffix(e.g. `-2`) is appended if the name is already taken, e.g. when multiple nodes are run on the same machine.
     ///
     /// This should be called before the `start()` function.
-    /// # Example
+    pub fn set_local_ctrl_instance_name(&mut self, name: &str) {
+        self.local_ctrl_instance_name = Some(name.to_string());
+    }
+
+    /// Exposes an additional property over local control, e.g. status of a node service.
+    ///
+    /// `get` returns the current value of property and `set` is invoked with the data written by the client.
+    /// Properties named `config` and `params` are reserved for node configuration and param values.
     /// ```rust
-    /// let rmaker = Rainmaker::init()?;
-    /// let mut node = Node::new(rmaker.get_node_id());
-    /// rmaker.register_node();
-    /// rmaker.start();
+    /// rmaker.add_local_ctrl_property(
+    ///     "time",
+    ///     || current_time().into_bytes(),
+    ///     |data| set_time(data),
+    /// );
     /// ```
     ///
-    pub fn register_node(&mut self, node: Node) {
-        self.node = Some(node.into());
+    /// This should be called before the `start()` function.
+    pub fn add_local_ctrl_property(
+        &mut self,
+        name: &str,
+        get: impl Fn() -> Vec<u8> + Send + Sync + 'static,
+        set: impl Fn(&[u8]) + Send + Sync + 'static,
+    ) {
+        self.local_ctrl_properties.push((
+            name.to_string(),
+            LocalCtrlProperty {
+                prop_type: LOCAL_CTRL_TYPE_CUSTOM,
+                get: Arc::new(get),
+                set: Some(Arc::new(set)),
+            },
+        ));
     }
 
-    /// Registers the endpoint used for claiming process with `WiFiProvMgr`. This is used for associating a RainMaker node with the user account performing the provisioning.
+    /// Same as [`add_local_ctrl_property`](Rainmaker::add_local_ctrl_property), for properties which can not be
+    /// modified by the client.
+    pub fn add_local_ctrl_readonly_property(
+        &mut self,
+        name: &str,
+        get: impl Fn() -> Vec<u8> + Send + Sync + 'static,
+    ) {
+        self.local_ctrl_properties.push((
+            name.to_string(),
+            LocalCtrlProperty {
+                prop_type: LOCAL_CTRL_TYPE_CUSTOM,
+                get: Arc::new(get),
+                set: None,
+            },
+        ));
+    }
+
+    /// Overrides broker address and TLS configuration(server CA, node certificate and key) used for connecting to MQTT broker.
     ///
-    /// This should be called before `WiFiProvMgr::start()`
-    pub fn reg_user_mapping_ep<T: WiFiProvTransportTrait>(&self, prov_mgr: &mut WifiProvMgr<T>) {
-        let node_id = self.get_node_id().to_string();
-        prov_mgr.add_endpoint(
-            "cloud_user_assoc",
-            Box::new(move |ep, data| -> Vec<u8> { cloud_user_assoc_callback(ep, data, &node_id) }),
-        )
+    /// This should be called before the `start()` function.
+    /// ```rust
+    /// rmaker.set_mqtt_tls_config(MqttTlsConfig {
+    ///     host: Some("mqtt.example.com".to_string()),
+    ///     server_cert: Some(include_bytes!("custom_ca.crt").to_vec()),
+    ///     ..Default::default()
+    /// })?;
+    /// ```
+    pub fn set_mqtt_tls_config(&self, config: MqttTlsConfig) -> Result<(), RmakerError> {
+        rmaker_mqtt::set_tls_config(config)?;
+        Ok(())
     }
 
-    #[cfg(target_os = "linux")]
-    fn linux_init_claimdata() {
-        let fctry_partition = NvsPartition::new("fctry").unwrap();
-        let mut rmaker_namespace = Nvs::new(fctry_partition, "rmaker_creds").unwrap();
-
-        let mut buff = vec![0; 2500];
-        let node_id = rmaker_namespace.get_bytes("node_id", &mut buff).unwrap();
-        let client_cert = rmaker_namespace
-            .get_bytes("client_cert", &mut buff)
-            .unwrap();
-        let client_key = rmaker_namespace.get_bytes("client_key", &mut buff).unwrap();
+    /// Overrides suffixes of MQTT topics, for RainMaker-compatible backends using a different topic scheme.
+    ///
+    /// This should be called before creating devices and starting the agent,
+    /// returns an error if any topic is already in use.
+    pub fn set_topic_config(&self, config: TopicConfig) -> Result<(), RmakerError> {
+        topics::set_topic_config(config)
+    }
 
-        if node_id.is_none() || client_cert.is_none() || client_key.is_none() {
-            let claimdata_notfound_error = "Please set RMAKER_CLAIMDATA_LOC env variable pointing to your rainmaker claimdata folder";
+    /// Sets encoding of node configuration and param payloads exchanged with RainMaker cloud. Defaults to JSON.
+    ///
+    /// This should be called before creating devices and starting the agent,
+    /// returns an error if any payload is already encoded.
+    /// ```rust
+    /// rmaker.set_payload_encoding(PayloadEncoding::Cbor)?;
+    /// ```
+    pub fn set_payload_encoding(&self, encoding: PayloadEncoding) -> Result<(), RmakerError> {
+        encoding::set_encoding(encoding)
+    }
 
-            let claimdata_loc = env::var("RMAKER_CLAIMDATA_PATH").expect(claimdata_notfound_error);
-            let claimdata_path = Path::new(claimdata_loc.as_str());
+    /// Sets handling of received numbers which do not fit the integer parameter they are sent for,
+    /// e.g. `50.7` or integers beyond the range of `i64`.
+    ///
+    /// Defaults to [`NumberHandling::Strict`]. This should be called before the `start()` function,
+    /// returns an error if any value is already received.
+    pub fn set_number_handling(&self, handling: NumberHandling) -> Result<(), RmakerError> {
+        param::set_number_handling(handling)
+    }
 
-            if !claimdata_path.exists() {
-                panic!("Claimdata folder doesn't exist");
-            }
+    /// Warns about MQTT payloads larger than the provided fraction of the message size limit of broker,
+    /// since brokers drop larger messages without an error on the node.
+    ///
+    /// Defaults to 80% of 128 KB, the message size limit of AWS IoT used by RainMaker.
+    /// Returns [`RmakerError::InvalidSizeWarningFraction`] unless `0.0 < fraction <= 1.0`.
+    /// This should be called before the `start()` function.
+    /// ```rust
+    /// rmaker.set_message_size_warning(256 * 1024, 0.9)?;
+    /// ```
+    pub fn set_message_size_warning(&self, limit: usize, fraction: f64) -> Result<(), RmakerError> {
+        // also rejects NaN
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(RmakerError::InvalidSizeWarningFraction(fraction));
+        }
+        rmaker_mqtt::set_message_size_warning(limit, fraction)?;
+        Ok(())
+    }
+
+    /// Overrides delays before reconnecting to MQTT broker after the connection is lost.
+    ///
+    /// Defaults to delays doubling from 2 seconds up to 5 minutes. See [ReconnectBackoff].
+    /// This should be called before the `start()` function.
+    pub fn set_reconnect_backoff(&self, backoff: ReconnectBackoff) -> Result<(), RmakerError> {
+        rmaker_mqtt::set_reconnect_backoff(backoff)?;
+        Ok(())
+    }
 
-            let node_id = claimdata_path.join("node.info");
-            let client_cert = claimdata_path.join("node.crt");
-            let client_key = claimdata_path.join("node.key");
-            let random = claimdata_path.join("random.info");
+    /// Stops the RainMaker agent.
+    ///
+    /// Unsubscribes from remote parameter updates, stops local control and heartbeat and disconnects from RainMaker cloud.
+    /// Agent can be started again using [`start`](Rainmaker::start).
+    pub fn stop(&mut self) -> Result<(), RmakerError> {
+        let remote_param_topic =
+            topics::node_topic(self.get_node_id(), &topics::config().params_remote);
 
-            if !node_id.exists() || !client_cert.exists() || !client_key.exists() {
-                panic!("Claimdata folder doesn't contain valid data");
-            }
+        let params_get_topic = topics::node_topic(self.get_node_id(), &topics::config().params_get);
+
+        self.heartbeat = None;
+        self.local_ctrl = None;
+        rmaker_mqtt::unsubscribe(&remote_param_topic)?;
+        rmaker_mqtt::unsubscribe(&params_get_topic)?;
+        rmaker_mqtt::disconnect()?;
+
+        Ok(())
+    }
+
+    /// Sets a callback which is invoked with every remote parameters message before it is dispatched to devices.
+    ///
+    /// Returning [`CommandDecision::Drop`] from the callback discards the message.
+    pub fn set_command_interceptor(&self, interceptor: CommandInterceptor) {
+        *self.command_interceptor.write().unwrap() = Some(interceptor);
+    }
+
+    /// Acknowledges commands carrying a `request_id` on `node/<node_id>/cmd_resp` once they are handled by devices.
+    ///
+    /// Acknowledgment contains the request id, current values of the params in command and a status,
+    /// one of `success`, `failure`(malformed command), `rejected`(dropped by command interceptor) or
+    /// `unknown_device`(command targets a device which is not registered, other devices are still updated).
+    /// ```json
+    /// {"request_id": "42", "status": "success", "params": {"Switch": {"Power": true}}}
+    /// ```
+    /// Disabled by default. This should be called before the `start()` function.
+    pub fn set_command_ack(&mut self, enable: bool) {
+        self.command_ack = enable;
+    }
+
+    /// Delivers parameter values to devices as if they were received from RainMaker cloud.
+    ///
+    /// Values should be in the same format as cloud commands, i.e. `{"<device>": {"<param>": <value>}}`.
+    /// Should be called after [`start`](Rainmaker::start).
+    /// ```rust
+    /// rmaker.simulate_remote_params(json!({"Switch": {"Power": true}}))?;
+    /// ```
+    pub fn simulate_remote_params(&self, params: Value) -> Result<(), RmakerError> {
+        let remote_param_topic =
+            topics::node_topic(self.get_node_id(), &topics::config().params_remote);
 
-            rmaker_namespace
-                .set_bytes("node_id", fs::read_to_string(node_id).unwrap().as_bytes())
-                .unwrap();
-            rmaker_namespace
-                .set_bytes(
-                    "client_cert",
-                    fs::read_to_string(client_cert).unwrap().as_bytes(),
-                )
-                .unwrap();
-            rmaker_namespace
-                .set_bytes(
-                    "client_key",
-                    fs::read_to_string(client_key).unwrap().as_bytes(),
-                )
-                .unwrap();
-            rmaker_namespace
-                .set_bytes("random", fs::read_to_string(random).unwrap().as_bytes())
-                .unwrap();
+        if !rmaker_mqtt::deliver(&remote_param_topic, encoding::encode(&params)) {
+            log::error!("agent not started. cannot deliver params");
+            return Err(RmakerError::UnknownError);
         }
+
+        Ok(())
     }
-}
 
-fn remote_params_callback(msg: ReceivedMessage, node: &Arc<Node>) {
-    let received_val: HashMap<String, HashMap<String, Value>> =
-        serde_json::from_str(&String::from_utf8(msg.payload).unwrap()).unwrap();
-    let devices = received_val.keys();
-    for device in devices {
-        let params = received_val.get(device).unwrap().to_owned();
-        node.exeute_device_callback(device, params);
+    /// Shuts down the RainMaker agent.
+    ///
+    /// Removes all MQTT subscriptions, drops queued messages, stops local control(including mDNS advertisement)
+    /// and heartbeat and disconnects from RainMaker cloud.
+    ///
+    /// Agent lives for the rest of the program and is never dropped, hence this should be called explicitly
+    /// before exiting or restarting.
+    pub fn shutdown(&mut self) -> Result<(), RmakerError> {
+        self.heartbeat = None;
+        self.wifi_monitor = None;
+        self.local_ctrl = None;
+
+        if rmaker_mqtt::is_mqtt_initialized() {
+            rmaker_mqtt::unsubscribe_all();
+            rmaker_mqtt::disconnect()?;
+        }
+
+        Ok(())
     }
-}
 
-fn cloud_user_assoc_callback(_ep: &str, data: &[u8], node_id: &str) -> Vec<u8> {
-    let req_proto = RMakerConfigPayload::try_from(data).unwrap();
-    let req_payload = req_proto.payload;
+    /// Publishes node configuration as a retained message, so that clients subscribing later receive it.
+    ///
+    /// Useful for self-hosted brokers and bridges. Disabled by default.
+    /// This should be called before the `start()` function.
+    pub fn set_retain_node_config(&mut self, retain: bool) {
+        self.retain_node_config = retain;
+    }
 
-    let (user_id, secret_key) = match req_payload {
-        mod_RMakerConfigPayload::OneOfpayload::cmd_set_user_mapping(p) => (p.UserID, p.SecretKey),
-        _ => unreachable!(),
-    };
+    /// Publishes node configuration on every start, even if it is unchanged since it was last published.
+    ///
+    /// By default, a hash of the configuration is stored in the runtime NVS partition(see
+    /// [`set_runtime_partition`](Rainmaker::set_runtime_partition)) once the configuration is published, and
+    /// configuration is published only when it differs.
+    /// This should be called before the `start()` function.
+    pub fn set_force_node_config(&mut self, force: bool) {
+        self.force_node_config = force;
+    }
 
-    log::info!("received user_id={}, secret_key={}", user_id, secret_key);
+    /// Sets the NVS partition in which runtime state of the agent(e.g. hash of published node configuration) is stored.
+    ///
+    /// The agent does not open any partition by itself, so that it does not conflict with the application. If no
+    /// partition is set, runtime state is not persisted and node configuration is published on every start.
+    /// This should be called before the `start()` function.
+    /// ```rust
+    /// let nvs_partition = NvsPartition::new("nvs")?;
+    /// rmaker.set_runtime_partition(nvs_partition.clone())?;
+    /// ```
+    pub fn set_runtime_partition(&self, partition: NvsPartition) -> Result<(), RmakerError> {
+        storage::init(partition)?;
+        Ok(())
+    }
 
-    let user_mapping_json = json!({
-        "node_id": node_id,
-        "user_id": user_id,
-        "secret_key": secret_key,
-        "reset": true
-    });
+    /// Sets maximum size of serialized node configuration and the action taken when it is exceeded.
+    ///
+    /// Configurations larger than the message size limit of the broker are dropped by it without an error.
+    /// Defaults to warning above 128 KB, the message size limit of AWS IoT used by RainMaker.
+    /// ```rust
+    /// rmaker.set_node_config_size_limit(32 * 1024, ConfigSizePolicy::Reject);
+    /// ```
+    pub fn set_node_config_size_limit(&mut self, max_bytes: usize, policy: ConfigSizePolicy) {
+        self.node_config_limit = (max_bytes, policy);
+    }
+
+    /// Returns true if agent is connected to RainMaker cloud.
+    pub fn is_connected(&self) -> bool {
+        rmaker_mqtt::is_mqtt_connected()
+    }
 
-    let user_mapping_topic = format!("node/{}/{}", node_id, USER_MAPPING_TOPIC_SUFFIX);
+    /// Blocks till agent is connected to RainMaker cloud.
+    ///
+    /// Returns [`RmakerError::Timeout`] if not connected within the provided duration.
+    /// ```rust
+    /// rmaker.start()?;
+    /// rmaker.wait_until_connected(Duration::from_secs(30))?;
+    /// ```
+    pub fn wait_until_connected(&self, timeout: Duration) -> Result<(), RmakerError> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-    if !rmaker_mqtt::is_mqtt_initialized() && rmaker_mqtt::init_rmaker_mqtt().is_err() {
-        // cannot publish user mapping payload
-        return vec![0];
+        let deadline = clock::now() + timeout;
+        while !self.is_connected() {
+            let now = clock::now();
+            if now >= deadline {
+                return Err(RmakerError::Timeout);
+            }
+            clock::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+
+        Ok(())
     }
 
-    if rmaker_mqtt::publish(
-        &user_mapping_topic,
-        user_mapping_json.to_string().as_bytes().to_vec(),
-    )
-    .is_err()
-    {
-        log::error!("could not publish user mapping payload");
+    /// Returns node id, registered devices and subscribed topics for field diagnostics.
+    /// ```rust
+    /// log::info!("{:#?}", rmaker.debug_state());
+    /// ```
+    pub fn debug_state(&self) -> DebugState {
+        let devices = match &self.node {
+            Some(node) => node
+                .read()
+                .unwrap()
+                .devices()
+                .iter()
+                .map(|d| d.name().to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        DebugState {
+            node_id: self.node_id.clone(),
+            devices,
+            subscribed_topics: rmaker_mqtt::subscribed_topics(),
+            connected: self.is_connected(),
+            largest_payload: rmaker_mqtt::largest_payload(),
+        }
     }
 
-    let res_proto = RMakerConfigPayload {
-        msg: RMakerConfigMsgType::TypeRespSetUserMapping,
-        payload: mod_RMakerConfigPayload::OneOfpayload::resp_set_user_mapping(RespSetUserMapping {
-            Status: RMakerConfigStatus::Success,
-            NodeId: node_id.to_string(),
-        }),
-    };
+    /// Starts publishing a keepalive payload to `node/<node_id>/heartbeat` at the provided interval.
+    ///
+    /// Calling this again restarts the heartbeat with the new interval.
+    /// Heartbeat is stopped on [`disable_heartbeat`](Rainmaker::disable_heartbeat).
+    pub fn enable_heartbeat(&mut self, interval: Duration) {
+        // stop existing heartbeat before starting a new one
+        self.heartbeat = None;
+        self.heartbeat = Some(Heartbeat::start(&self.node_id, interval));
+    }
 
-    let mut out_vec = vec![];
-    let mut writer = Writer::new(&mut out_vec);
+    /// Stops publishing heartbeat.
+    pub fn disable_heartbeat(&mut self) {
+        self.heartbeat = None;
+    }
 
-    res_proto.write_message(&mut writer).unwrap();
+    /// Raises an alert which is shown as a notification in the RainMaker phone apps.
+    ///
+    /// Publishes `{"esp.alert.str": <message>}` to `node/<node_id>/alert`.
+    /// ```rust
+    /// rmaker.raise_alert("Filter needs cleaning")?;
+    /// ```
+    pub fn raise_alert(&self, message: &str) -> Result<(), RmakerError> {
+        let topic = topics::node_topic(self.get_node_id(), &topics::config().alert);
+        let payload = json!({ "esp.alert.str": message });
 
-    out_vec
+        rmaker_mqtt::publish(&topic, payload.to_string().into_bytes())?;
+        Ok(())
+    }
+
+    /// Publishes payload to a custom topic `node/<node_id>/<suffix>`, e.g. for bridging with other systems.
+    ///
+    /// Returns [`RmakerError::InvalidTopic`] if suffix is empty, starts with `/` or contains wildcards.
+    /// ```rust
+    /// rmaker.publish_custom("bridge/status", b"online".to_vec())?;
+    /// ```
+    pub fn publish_custom(&self, suffix: &str, payload: Vec<u8>) -> Result<(), RmakerError> {
+        if suffix.contains(['+', '#']) {
+            return Err(RmakerError::InvalidTopic(suffix.to_string()));
+        }
+        let topic = self.custom_topic(suffix)?;
+
+        rmaker_mqtt::publish(&topic, payload)?;
+        Ok(())
+    }
+
+    /// Subscribes to a custom topic `node/<node_id>/<suffix>`. Suffix may contain MQTT wildcards(`+`, `#`).
+    ///
+    /// Callback is invoked with every message received on the topic.
+    /// ```rust
+    /// rmaker.subscribe_custom("bridge/cmd", |msg| {
+    ///     log::info!("received on {}: {:?}", msg.topic, msg.payload);
+    /// })?;
+    /// ```
+    ///
+    /// This should be called after the `start()` function.
+    pub fn subscribe_custom(
+        &self,
+        suffix: &str,
+        cb: impl Fn(ReceivedMessage) + Send + Sync + 'static,
+    ) -> Result<(), RmakerError> {
+        let topic = self.custom_topic(suffix)?;
+
+        rmaker_mqtt::subscribe(&topic, cb)?;
+        Ok(())
+    }
+
+    /// Subscribes to a custom topic `node/<node_id>/<suffix>` of a request/response flow.
+    ///
+    /// Callback returns the reply for the received message as `(topic, payload)`, which is then published by the agent.
+    /// No reply is published if the callback returns `None`.
+    /// ```rust
+    /// let reply_topic = format!("node/{}/bridge/status", rmaker.get_node_id());
+    /// rmaker.subscribe_custom_with_reply("bridge/status/get", move |_msg| {
+    ///     Some((reply_topic.clone(), b"online".to_vec()))
+    /// })?;
+    /// ```
+    ///
+    /// This should be called after the `start()` function.
+    pub fn subscribe_custom_with_reply(
+        &self,
+        suffix: &str,
+        cb: impl Fn(ReceivedMessage) -> Option<(String, Vec<u8>)> + Send + Sync + 'static,
+    ) -> Result<(), RmakerError> {
+        let topic = self.custom_topic(suffix)?;
+
+        rmaker_mqtt::subscribe_with_reply(&topic, cb)?;
+        Ok(())
+    }
+
+    fn custom_topic(&self, suffix: &str) -> Result<String, RmakerError> {
+        if suffix.is_empty() || suffix.starts_with('/') {
+            return Err(RmakerError::InvalidTopic(suffix.to_string()));
+        }
+
+        Ok(topics::node_topic(self.get_node_id(), suffix))
+    }
+
+    /// Starts checking WiFi connection at the provided interval.
+    ///
+    /// While WiFi is disconnected, reports are queued instead of being published and reconnection is triggered.
+    /// Queued reports are published once WiFi is connected again. On Linux, network is assumed to be always connected.
+    pub fn enable_wifi_monitor(&mut self, interval: Duration) {
+        // stop existing monitor before starting a new one
+        self.wifi_monitor = None;
+        self.wifi_monitor = Some(WifiMonitor::start(interval));
+    }
+
+    /// Stops checking WiFi connection and resumes publishing.
+    pub fn disable_wifi_monitor(&mut self) {
+        self.wifi_monitor = None;
+    }
+
+    /// Scans for nearby WiFi access points, e.g. for offering networks to the user during provisioning.
+    ///
+    /// See [wifi::scan].
+    pub fn scan_wifi(&self) -> Result<Vec<wifi::ApInfo>, RmakerError> {
+        wifi::scan()
+    }
+
+    /// Publishes current values of all params as initial params again, e.g. when the backend has reset its state
+    /// or after user-node mapping is reset.
+    ///
+    /// This should be called after the `start()` function.
+    pub fn report_all_params(&self) -> Result<(), RmakerError> {
+        let node = match &self.node {
+            Some(node) => node,
+            None => return Err(RmakerError::NodeNotRegistered),
+        };
+
+        let (topic, payload, retain) =
+            init_params_message(&node.read().unwrap(), self.get_node_id())?;
+        rmaker_mqtt::publish_with_retain(&topic, payload, retain)?;
+
+        Ok(())
+    }
+
+    /// Registers node to agent.
+    ///
+    /// This should be called before the `start()` function.
+    /// # Example
+    /// ```rust
+    /// let rmaker = Rainmaker::init()?;
+    /// let mut node = Node::new(rmaker.get_node_id());
+    /// rmaker.register_node();
+    /// rmaker.start();
+    /// ```
+    ///
+    pub fn register_node(&mut self, node: Node) {
+        self.node = Some(Arc::new(RwLock::new(node)));
+    }
+
+    /// Provides mutable access to the registered node, e.g. for adding or removing devices after the agent is started.
+    ///
+    /// Returns `None` if no node is registered. Call [`update_node_config`](Rainmaker::update_node_config) afterwards
+    /// to inform RainMaker cloud about the changes.
+    /// ```rust
+    /// rmaker.with_node(|node| node.add_device(device))?;
+    /// rmaker.update_node_config()?;
+    /// ```
+    ///
+    /// This should not be called from a device callback.
+    pub fn with_node<R>(&self, f: impl FnOnce(&mut Node) -> R) -> Option<R> {
+        self.node.as_ref().map(|node| f(&mut node.write().unwrap()))
+    }
+
+    /// Returns the registered node, or `None` if no node is registered.
+    ///
+    /// Unlike [`with_node`](Rainmaker::with_node), the returned handle can be kept, e.g. for reporting params
+    /// from a different thread. Locks should not be held while agent may dispatch commands to devices.
+    /// ```rust
+    /// let node = rmaker.node().unwrap();
+    /// let reporter = node.read().unwrap().devices()[0].reporter();
+    /// reporter.report_param("Power", ParamValue::Bool(true))?;
+    /// ```
+    pub fn node(&self) -> Option<Arc<RwLock<Node>>> {
+        self.node.clone()
+    }
+
+    /// Publishes the current node configuration and parameter values to RainMaker cloud.
+    ///
+    /// Should be called after devices are modified using [`with_node`](Rainmaker::with_node).
+    pub fn update_node_config(&self) -> Result<(), RmakerError> {
+        let node = match &self.node {
+            Some(node) => node,
+            None => return Err(RmakerError::NodeNotRegistered),
+        };
+
+        let node_config = initial_messages(
+            &node.read().unwrap(),
+            self.get_node_id(),
+            self.retain_node_config,
+            true,
+            self.node_config_limit,
+        )?
+        .publish()?;
+        self.set_published_config(node_config);
+
+        Ok(())
+    }
+
+    /// Publishes only the changes in node configuration since it was last published, as a JSON Patch(RFC 6902),
+    /// e.g. `[{"op": "replace", "path": "/devices/0/params/1/bounds/max", "value": 100}]`.
+    ///
+    /// Patch is published on `node/<node_id>/config/patch`, hence requires a backend which supports merging
+    /// configuration patches. Full configuration is published if it was not published since the agent started.
+    /// Current values of params are not reported.
+    /// ```rust
+    /// rmaker.with_node(|node| node.add_device(device))?;
+    /// rmaker.update_node_config_partial()?;
+    /// ```
+    pub fn update_node_config_partial(&self) -> Result<(), RmakerError> {
+        let node = match &self.node {
+            Some(node) => node,
+            None => return Err(RmakerError::NodeNotRegistered),
+        };
+
+        let published_config = self.published_config.lock().unwrap().clone();
+        let published_config = match published_config {
+            Some(config) => config,
+            None => return self.update_node_config(),
+        };
+
+        let (node_config, config_hash) = hashed_node_config(&node.read().unwrap())?;
+        let patch = utils::json_patch(&published_config, &node_config);
+        if patch.is_empty() {
+            log::info!("node configuration unchanged. not publishing");
+            return Ok(());
+        }
+
+        let patch = Value::from(patch);
+        log::info!("publishing nodeconfig patch: {}", patch);
+        let topic = topics::node_topic(self.get_node_id(), &topics::config().config_patch);
+        rmaker_mqtt::publish_with_retain_then(
+            &topic,
+            encoding::encode(&patch),
+            false,
+            move || store_config_hash(config_hash),
+        )?;
+        self.set_published_config(node_config);
+
+        Ok(())
+    }
+
+    // base for computing partial updates. hash is stored separately once configuration reaches the broker
+    fn set_published_config(&self, node_config: Value) {
+        *self.published_config.lock().unwrap() = Some(node_config);
+    }
+
+    /// Registers the endpoint used for claiming process with `WiFiProvMgr`. This is used for associating a RainMaker node with the user account performing the provisioning.
+    ///
+    /// This should be called before `WiFiProvMgr::start()`
+    pub fn reg_user_mapping_ep<T: WiFiProvTransportTrait>(&self, prov_mgr: &mut WifiProvMgr<T>) {
+        let node_id = self.get_node_id().to_string();
+        let prov_event_cb = self.prov_event_cb.clone();
+        prov_mgr.add_endpoint(
+            "cloud_user_assoc",
+            Box::new(move |ep, data| -> Vec<u8> {
+                cloud_user_assoc_callback(ep, data, &node_id, &prov_event_cb)
+            }),
+        )
+    }
+
+    /// Runs WiFi provisioning and blocks till the node is connected to the access point using received credentials.
+    ///
+    /// Steps are reported to the callback set using [`set_prov_event_callback`](Rainmaker::set_prov_event_callback):
+    /// [`Started`](ProvisioningEvent::Started) once provisioning is started,
+    /// [`CredsReceived`](ProvisioningEvent::CredsReceived) and [`Connecting`](ProvisioningEvent::Connecting) once
+    /// credentials are received, and [`ConnectedToAp`](ProvisioningEvent::ConnectedToAp) once WiFi is connected.
+    /// Returns [`RmakerError::Timeout`] and reports [`Failed`](ProvisioningEvent::Failed) if WiFi is not connected
+    /// within the provided duration.
+    /// ```rust
+    /// rmaker.reg_user_mapping_ep(&mut prov_mgr);
+    /// rmaker.provision(&mut prov_mgr, Duration::from_secs(30))?;
+    /// ```
+    ///
+    /// Endpoints should be registered before calling this function.
+    pub fn provision<T: WiFiProvTransportTrait>(
+        &self,
+        prov_mgr: &mut WifiProvMgr<T>,
+        timeout: Duration,
+    ) -> Result<(), RmakerError> {
+        if prov_mgr.start().is_err() {
+            emit_prov_event(
+                &self.prov_event_cb,
+                ProvisioningEvent::Failed("could not start provisioning".to_string()),
+            );
+            return Err(RmakerError::UnknownError);
+        }
+        emit_prov_event(&self.prov_event_cb, ProvisioningEvent::Started);
+
+        prov_mgr.wait_for_provisioning();
+        emit_prov_event(&self.prov_event_cb, ProvisioningEvent::CredsReceived);
+        emit_prov_event(&self.prov_event_cb, ProvisioningEvent::Connecting);
+
+        if !wifi_monitor::wait_for_wifi(timeout) {
+            emit_prov_event(
+                &self.prov_event_cb,
+                ProvisioningEvent::Failed("could not connect to access point".to_string()),
+            );
+            return Err(RmakerError::Timeout);
+        }
+        emit_prov_event(&self.prov_event_cb, ProvisioningEvent::ConnectedToAp);
+
+        Ok(())
+    }
+
+    /// Registers an endpoint with `WiFiProvMgr` which returns nearby WiFi access points, sorted by signal strength.
+    ///
+    /// Endpoint is named [`wifi::WIFI_SCAN_ENDPOINT`] and responds with JSON, e.g.
+    /// ```json
+    /// {"status": "success", "aps": [{"ssid": "home", "bssid": "aa:bb:cc:dd:ee:ff", "rssi": -40, "channel": 6, "open": false}]}
+    /// ```
+    /// Status is `unsupported` on Linux and `failure` if the scan could not be performed.
+    ///
+    /// This should be called before `WiFiProvMgr::start()`
+    pub fn reg_wifi_scan_ep<T: WiFiProvTransportTrait>(&self, prov_mgr: &mut WifiProvMgr<T>) {
+        prov_mgr.add_endpoint(
+            wifi::WIFI_SCAN_ENDPOINT,
+            Box::new(|_ep, _data| -> Vec<u8> { wifi::scan_response() }),
+        )
+    }
+
+    /// Sets a callback which is invoked with events of provisioning steps handled by the agent, e.g. for updating
+    /// provisioning UI once the node is associated with the user.
+    /// ```rust
+    /// rmaker.set_prov_event_callback(Box::new(|event| match event {
+    ///     ProvisioningEvent::CloudAssocDone => log::info!("node associated with user"),
+    ///     event => log::warn!("provisioning event: {:?}", event),
+    /// }));
+    /// ```
+    pub fn set_prov_event_callback(&self, cb: ProvEventCb) {
+        *self.prov_event_cb.write().unwrap() = Some(cb);
+    }
+
+    /// Registers a custom endpoint with `WiFiProvMgr`, e.g. for device specific configuration during provisioning.
+    ///
+    /// Handler is invoked with the endpoint name and received data, and returns the response to be sent.
+    /// ```rust
+    /// rmaker.add_prov_endpoint(&mut prov_mgr, "custom-config", |_ep, data| {
+    ///     log::info!("received config: {:?}", data);
+    ///     b"ok".to_vec()
+    /// });
+    /// ```
+    ///
+    /// This should be called before `WiFiProvMgr::start()`
+    pub fn add_prov_endpoint<T: WiFiProvTransportTrait>(
+        &self,
+        prov_mgr: &mut WifiProvMgr<T>,
+        name: &str,
+        handler: impl Fn(&str, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+    ) {
+        register_prov_endpoint(prov_mgr, name, handler)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn linux_init_claimdata(profile: Option<&str>) -> Result<(), RmakerError> {
+        let rmaker_namespace = factory::get_nvs()?;
+
+        let node_id = factory::read_bytes(&rmaker_namespace, "node_id");
+        let client_cert = factory::read_bytes(&rmaker_namespace, "client_cert");
+        let client_key = factory::read_bytes(&rmaker_namespace, "client_key");
+
+        if let (Ok(Some(_)), Ok(Some(_)), Ok(Some(_))) = (node_id, client_cert, client_key) {
+            return Ok(());
+        }
+
+        let claim_data = profile_claimdata(profile)?;
+        factory::set_claim_data(&claim_data)?;
+
+        Ok(())
+    }
+}
+
+// claim data from folder pointed by RMAKER_CLAIMDATA_PATH, or RMAKER_CLAIMDATA_PATH_<PROFILE> for a profile
+#[cfg(target_os = "linux")]
+fn profile_claimdata(profile: Option<&str>) -> Result<ClaimData, RmakerError> {
+    let claimdata_env = match profile {
+        Some(profile) => format!("RMAKER_CLAIMDATA_PATH_{}", profile.to_uppercase()),
+        None => "RMAKER_CLAIMDATA_PATH".to_string(),
+    };
+    let claimdata_loc = match env::var(&claimdata_env) {
+        Ok(loc) => loc,
+        Err(_) => {
+            log::error!(
+                "Please set {} env variable pointing to your rainmaker claimdata folder",
+                claimdata_env
+            );
+            return Err(RmakerError::ClaimDataMissing);
+        }
+    };
+
+    read_claimdata_folder(Path::new(claimdata_loc.as_str()), profile)
+}
+
+// claim data from files in claimdata folder, either separate certificate and key or a PKCS#12 bundle
+#[cfg(target_os = "linux")]
+fn read_claimdata_folder(
+    claimdata_path: &Path,
+    profile: Option<&str>,
+) -> Result<ClaimData, RmakerError> {
+    if !claimdata_path.exists() {
+        log::error!("Claimdata folder doesn't exist");
+        return Err(RmakerError::ClaimDataMissing);
+    }
+
+    let node_id = claimdata_path.join("node.info");
+    let client_cert = claimdata_path.join("node.crt");
+    let client_key = claimdata_path.join("node.key");
+    let random = claimdata_path.join("random.info");
+
+    let read_claim_file = |path: &Path| -> Result<String, RmakerError> {
+        fs::read_to_string(path).map_err(|_| RmakerError::ClaimDataInvalid)
+    };
+
+    let (cert, key) = if node_id.exists() && client_cert.exists() && client_key.exists() {
+        (
+            read_claim_file(&client_cert)?,
+            read_claim_file(&client_key)?,
+        )
+    } else {
+        #[cfg(feature = "pkcs12")]
+        let credentials = match claimdata_path.join("node.p12") {
+            bundle if node_id.exists() && bundle.exists() => {
+                let password_env = match profile {
+                    Some(profile) => {
+                        format!("RMAKER_CLAIMDATA_P12_PASSWORD_{}", profile.to_uppercase())
+                    }
+                    None => "RMAKER_CLAIMDATA_P12_PASSWORD".to_string(),
+                };
+                let password = env::var(password_env).unwrap_or_default();
+                Some(read_pkcs12_credentials(&bundle, &password)?)
+            }
+            _ => None,
+        };
+        #[cfg(not(feature = "pkcs12"))]
+        let credentials = {
+            // profile only selects password of PKCS#12 bundle
+            let _ = profile;
+            None
+        };
+
+        match credentials {
+            Some(credentials) => credentials,
+            None => {
+                log::error!("Claimdata folder doesn't contain valid data");
+                return Err(RmakerError::ClaimDataInvalid);
+            }
+        }
+    };
+
+    Ok(ClaimData {
+        node_id: read_claim_file(&node_id)?,
+        cert,
+        key,
+        random: read_claim_file(&random)?,
+    })
+}
+
+// node certificate and key from a PKCS#12 bundle, in PEM format
+#[cfg(all(target_os = "linux", feature = "pkcs12"))]
+fn read_pkcs12_credentials(path: &Path, password: &str) -> Result<(String, String), RmakerError> {
+    let bundle = fs::read(path).map_err(|_| RmakerError::ClaimDataInvalid)?;
+    let pfx = p12::PFX::parse(&bundle).map_err(|_| RmakerError::ClaimDataInvalid)?;
+
+    // first certificate is of the node, rest are of the chain, if any
+    let cert = pfx
+        .cert_x509_bags(password)
+        .ok()
+        .and_then(|c| c.into_iter().next());
+    let key = pfx
+        .key_bags(password)
+        .ok()
+        .and_then(|k| k.into_iter().next());
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok((
+            utils::pem_encode("CERTIFICATE", &cert),
+            utils::pem_encode("PRIVATE KEY", &key),
+        )),
+        _ => {
+            log::error!(
+                "could not read certificate and key from PKCS#12 bundle. check the password"
+            );
+            Err(RmakerError::ClaimDataInvalid)
+        }
+    }
+}
+
+fn connect_mqtt() -> Result<(), RmakerError> {
+    // initialize mqtt if not done already
+    if !rmaker_mqtt::is_mqtt_initialized() {
+        rmaker_mqtt::init_rmaker_mqtt()?;
+    } else {
+        // connect again if stopped previously
+        rmaker_mqtt::reconnect()?;
+    }
+
+    Ok(())
+}
+
+// node configuration and initial param values to be reported on start, as (topic, payload, retain)
+struct InitialMessages {
+    // skipped if configuration is unchanged since it was last published, unless forced
+    node_config_message: Option<(String, Vec<u8>, bool)>,
+    init_params_message: (String, Vec<u8>, bool),
+    node_config: Value,
+    config_hash: u64,
+}
+
+impl InitialMessages {
+    // hash is stored only once configuration is published, so that a configuration lost while queued is
+    // published again on next start. returns the configuration
+    fn publish(self) -> Result<Value, RmakerError> {
+        if let Some((topic, payload, retain)) = self.node_config_message {
+            let config_hash = self.config_hash;
+            rmaker_mqtt::publish_with_retain_then(&topic, payload, retain, move || {
+                store_config_hash(config_hash)
+            })?;
+        }
+
+        let (topic, payload, retain) = self.init_params_message;
+        rmaker_mqtt::publish_with_retain(&topic, payload, retain)?;
+
+        Ok(self.node_config)
+    }
+}
+
+fn initial_messages(
+    node: &Node,
+    node_id: &str,
+    retain_config: bool,
+    force_config: bool,
+    (size_limit, size_policy): (usize, ConfigSizePolicy),
+) -> Result<InitialMessages, RmakerError> {
+    let node_config_topic = topics::node_topic(node_id, &topics::config().config);
+
+    let mut node_config_message = None;
+
+    let (node_config, config_hash) = hashed_node_config(node)?;
+    if force_config || config_changed(config_hash, storage::get_config_hash()) {
+        log::info!("publishing nodeconfig: {}", node_config);
+        let payload = encoding::encode(&node_config);
+        if payload.len() > size_limit {
+            let err = RmakerError::NodeConfigTooLarge {
+                size: payload.len(),
+                limit: size_limit,
+            };
+            match size_policy {
+                ConfigSizePolicy::Warn => log::warn!("{}. broker may drop it", err),
+                ConfigSizePolicy::Reject => return Err(err),
+            }
+        }
+        node_config_message = Some((node_config_topic, payload, retain_config));
+    } else {
+        log::info!("node configuration unchanged. not publishing");
+    }
+
+    Ok(InitialMessages {
+        node_config_message,
+        init_params_message: init_params_message(node, node_id)?,
+        node_config,
+        config_hash,
+    })
+}
+
+// snapshot of all param values on the params/local/init topic
+fn init_params_message(node: &Node, node_id: &str) -> Result<(String, Vec<u8>, bool), RmakerError> {
+    let params_local_init_topic = topics::node_topic(node_id, &topics::config().params_local_init);
+    let init_params = serde_json::to_value(node.get_param_values()?)?;
+    log::info!("publishing initial params: {}", init_params);
+
+    Ok((
+        params_local_init_topic,
+        encoding::encode(&init_params),
+        false,
+    ))
+}
+
+// serializes node configuration along with its hash, used for detecting changes across restarts
+fn hashed_node_config(node: &Node) -> Result<(Value, u64), RmakerError> {
+    let mut node_config = serde_json::to_value(node)?;
+    node_config["config_version"] = Value::from(NODE_CONFIG_VERSION);
+    let config_hash = utils::fnv1a_hash(node_config.to_string().as_bytes());
+
+    Ok((node_config, config_hash))
+}
+
+// hash is not stored before the first publish
+fn config_changed(config_hash: u64, stored_hash: Option<u64>) -> bool {
+    stored_hash != Some(config_hash)
+}
+
+// nothing is stored if application did not provide a runtime partition
+fn store_config_hash(config_hash: u64) {
+    match storage::set_config_hash(config_hash) {
+        Ok(()) | Err(RmakerStorageError::NotInitialized) => {}
+        Err(err) => log::warn!("could not store hash of node configuration: {}", err),
+    }
+}
+
+// reports current param values after reconnect since cloud may have missed updates while disconnected
+fn set_reconnect_reporting(node: SharedNode, node_id: String) {
+    let params_local_topic = topics::node_topic(&node_id, &topics::config().params_local);
+    rmaker_mqtt::set_reconnect_callback(move || report_current_params(&node, &params_local_topic));
+}
+
+// publishes snapshot of all param values, e.g. after reconnect
+fn report_current_params(node: &SharedNode, params_local_topic: &str) {
+    if let Some(params) = current_params(node) {
+        if rmaker_mqtt::publish(params_local_topic, params).is_err() {
+            log::error!("could not report current params");
+        }
+    }
+}
+
+// encoded snapshot of all param values, also the reply to requests for current params
+fn current_params(node: &SharedNode) -> Option<Vec<u8>> {
+    let params = match node.read().unwrap().get_param_values() {
+        Ok(params) => json!(params),
+        Err(err) => {
+            log::error!("could not report current params: {}", err);
+            return None;
+        }
+    };
+    log::info!("reporting current params: {}", params);
+    Some(encoding::encode(&params))
+}
+
+fn remote_params_callback(
+    msg: ReceivedMessage,
+    node: &SharedNode,
+    interceptor: &RwLock<Option<CommandInterceptor>>,
+    ack_topic: Option<&str>,
+) {
+    let malformed_params = |payload: &[u8]| {
+        log::error!(
+            "ignoring malformed remote params: {}",
+            String::from_utf8_lossy(payload)
+        );
+    };
+
+    let mut params = match encoding::decode(&msg.payload) {
+        Some(params) => params,
+        None => return malformed_params(&msg.payload),
+    };
+    let request_id = params
+        .as_object_mut()
+        .and_then(|params| params.remove(COMMAND_REQUEST_ID_KEY))
+        .map(|id| match id {
+            Value::String(id) => id,
+            id => id.to_string(),
+        });
+
+    let command = RawCommand {
+        topic: msg.topic,
+        params,
+        request_id,
+    };
+    let ack = |node: &Node, status: &str| {
+        if let (Some(ack_topic), Some(request_id)) = (ack_topic, &command.request_id) {
+            publish_command_ack(node, ack_topic, request_id, &command.params, status);
+        }
+    };
+
+    if let Some(interceptor) = interceptor.read().unwrap().as_ref() {
+        if interceptor(&command) == CommandDecision::Drop {
+            log::info!("remote params dropped by command interceptor");
+            return ack(&node.read().unwrap(), "rejected");
+        }
+    }
+
+    {
+        let node = node.read().unwrap();
+        match node.dispatch_params(command.params.clone()) {
+            Ok(unknown_devices) if unknown_devices.is_empty() => ack(&node, "success"),
+            Ok(_) => ack(&node, "unknown_device"),
+            Err(_) => {
+                malformed_params(&msg.payload);
+                ack(&node, "failure");
+            }
+        }
+    }
+    republish_changed_node_config(node);
+}
+
+// acknowledges command with current values of the params it carried
+fn publish_command_ack(node: &Node, topic: &str, request_id: &str, command: &Value, status: &str) {
+    let mut applied = serde_json::Map::new();
+    if let (Ok(values), Some(command)) = (node.get_param_values(), command.as_object()) {
+        for (device, params) in command {
+            let (Some(current), Some(params)) = (values.get(device.as_str()), params.as_object())
+            else {
+                continue;
+            };
+            let device_values = params
+                .keys()
+                .filter_map(|param| Some((param.clone(), current.get(param.as_str())?.clone())))
+                .collect();
+            applied.insert(device.clone(), Value::Object(device_values));
+        }
+    }
+
+    let ack = json!({
+        "request_id": request_id,
+        "status": status,
+        "params": applied
+    });
+    if rmaker_mqtt::publish(topic, encoding::encode(&ack)).is_err() {
+        log::error!("could not acknowledge request {}", request_id);
+    }
+}
+
+// delivers the event to provisioning event callback, if set
+fn emit_prov_event(prov_event_cb: &RwLock<Option<ProvEventCb>>, event: ProvisioningEvent) {
+    if let Some(cb) = prov_event_cb.read().unwrap().as_ref() {
+        cb(event);
+    }
+}
+
+fn register_prov_endpoint(
+    registry: &mut impl ProvEndpointRegistry,
+    name: &str,
+    handler: impl Fn(&str, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+) {
+    registry.add_endpoint(name, Box::new(handler))
+}
+
+fn cloud_user_assoc_callback(
+    _ep: &str,
+    data: &[u8],
+    node_id: &str,
+    prov_event_cb: &Arc<RwLock<Option<ProvEventCb>>>,
+) -> Vec<u8> {
+    let failed = |reason: String, status: RMakerConfigStatus| {
+        log::error!("{}", reason);
+        emit_prov_event(prov_event_cb, ProvisioningEvent::Failed(reason));
+        user_mapping_response(node_id, status)
+    };
+
+    let (user_id, secret_key) = match RMakerConfigPayload::try_from(data).map(|req| req.payload) {
+        Ok(mod_RMakerConfigPayload::OneOfpayload::cmd_set_user_mapping(p)) => {
+            (p.UserID, p.SecretKey)
+        }
+        _ => {
+            return failed(
+                "malformed user mapping request".to_string(),
+                RMakerConfigStatus::InvalidParam,
+            )
+        }
+    };
+
+    log::info!("received user_id={}, secret_key={}", user_id, secret_key);
+
+    let user_mapping_json = json!({
+        "node_id": node_id,
+        "user_id": user_id,
+        "secret_key": secret_key,
+        "reset": true
+    });
+
+    let user_mapping_topic = topics::node_topic(node_id, &topics::config().user_mapping);
+
+    if !rmaker_mqtt::is_mqtt_initialized() && rmaker_mqtt::init_rmaker_mqtt().is_err() {
+        // cannot publish user mapping payload
+        return failed(
+            "could not initialize mqtt".to_string(),
+            RMakerConfigStatus::InvalidState,
+        );
+    }
+
+    // mapping is queued till mqtt is connected, hence reported done only once it is published
+    let prov_event_cb_2 = prov_event_cb.clone();
+    if let Err(err) = rmaker_mqtt::publish_with_retain_then(
+        &user_mapping_topic,
+        user_mapping_json.to_string().as_bytes().to_vec(),
+        false,
+        move || emit_prov_event(&prov_event_cb_2, ProvisioningEvent::CloudAssocDone),
+    ) {
+        return failed(
+            format!("could not publish user mapping payload: {}", err),
+            RMakerConfigStatus::InvalidState,
+        );
+    }
+
+    user_mapping_response(node_id, RMakerConfigStatus::Success)
+}
+
+fn user_mapping_response(node_id: &str, status: RMakerConfigStatus) -> Vec<u8> {
+    let res_proto = RMakerConfigPayload {
+        msg: RMakerConfigMsgType::TypeRespSetUserMapping,
+        payload: mod_RMakerConfigPayload::OneOfpayload::resp_set_user_mapping(RespSetUserMapping {
+            Status: status,
+            NodeId: node_id.to_string(),
+        }),
+    };
+
+    let mut out_vec = vec![];
+    let mut writer = Writer::new(&mut out_vec);
+
+    if res_proto.write_message(&mut writer).is_err() {
+        log::error!("could not serialize user mapping response");
+    }
+
+    out_vec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{Device, DeviceHandle, DeviceType};
+    use crate::node::NodeBuilder;
+    use crate::param::{Param, ParamValue};
+    use std::collections::HashMap;
+
+    fn light_node(fw_version: &str) -> Node {
+        light_node_with_brightness(fw_version, Param::new_brightness("Brightness", 50))
+    }
+
+    fn light_node_with_brightness(fw_version: &str, brightness: Param) -> Node {
+        let mut device = Device::new("Light", DeviceType::Lightbulb);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device.add_param(brightness).unwrap();
+
+        NodeBuilder::new("node-1".to_string())
+            .info("Example Node", fw_version)
+            .device(device)
+            .build()
+            .unwrap()
+    }
+
+    // light which reports back received values
+    #[cfg(feature = "mock-mqtt")]
+    fn reporting_light_node() -> Node {
+        let mut device = Device::new("Light", DeviceType::Lightbulb);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device.register_callback(Box::new(|params, handle: DeviceHandle| {
+            handle.update_and_report(params).unwrap();
+        }));
+
+        NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "v1.0")
+            .device(device)
+            .build()
+            .unwrap()
+    }
+
+    // agent is not stored in the process wide singleton, so that tests can start their own
+    #[cfg(feature = "mock-mqtt")]
+    fn started_agent(node: Node) -> Rainmaker {
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(node);
+        rmaker.start().unwrap();
+        rmaker_mqtt::mock::take_published();
+        rmaker
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn simulated_params_are_handled_by_device_callback() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/params/local");
+        assert_eq!(
+            encoding::decode(&published[0].payload),
+            Some(json!({"Light": {"Power": true}}))
+        );
+        let params = current_params(&rmaker.node().unwrap()).unwrap();
+        assert_eq!(
+            encoding::decode(&params),
+            Some(json!({"Light": {"Power": true}}))
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn command_with_request_id_is_acknowledged() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.set_command_ack(true);
+        rmaker.register_node(reporting_light_node());
+        rmaker.start().unwrap();
+        rmaker_mqtt::mock::take_published();
+
+        rmaker
+            .simulate_remote_params(json!({"request_id": "42", "Light": {"Power": true}}))
+            .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        let ack = published
+            .iter()
+            .find(|m| m.topic == "node/node-1/cmd_resp")
+            .unwrap();
+        assert_eq!(
+            encoding::decode(&ack.payload),
+            Some(json!({
+                "request_id": "42",
+                "status": "success",
+                "params": {"Light": {"Power": true}}
+            }))
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn command_without_request_id_is_not_acknowledged() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.set_command_ack(true);
+        rmaker.register_node(reporting_light_node());
+        rmaker.start().unwrap();
+        rmaker_mqtt::mock::take_published();
+
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert!(!published.iter().any(|m| m.topic == "node/node-1/cmd_resp"));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn reported_params_match_initial_params_of_start() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+        rmaker.start().unwrap();
+        let init_topic = "node/node-1/params/local/init";
+        let on_start = rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .find(|m| m.topic == init_topic)
+            .unwrap();
+
+        rmaker.report_all_params().unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, init_topic);
+        assert_eq!(published[0].payload, on_start.payload);
+        assert_eq!(published[0].retain, on_start.retain);
+    }
+
+    #[test]
+    fn reporting_params_requires_registered_node() {
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+
+        assert!(matches!(
+            rmaker.report_all_params(),
+            Err(RmakerError::NodeNotRegistered)
+        ));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn oversized_node_config_is_rejected_if_enabled() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+        rmaker.set_node_config_size_limit(64, ConfigSizePolicy::Reject);
+
+        assert!(matches!(
+            rmaker.start(),
+            Err(RmakerError::NodeConfigTooLarge { size, limit: 64 }) if size > 64
+        ));
+        assert!(rmaker_mqtt::mock::take_published().is_empty());
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn oversized_node_config_is_published_with_warning() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+        rmaker.set_node_config_size_limit(64, ConfigSizePolicy::Warn);
+
+        rmaker.start().unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert!(published
+            .iter()
+            .any(|msg| msg.topic == "node/node-1/config" && msg.payload.len() > 64));
+    }
+
+    #[test]
+    fn node_is_none_before_registration() {
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+
+        assert!(rmaker.node().is_none());
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn param_is_reported_through_retrieved_node() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+
+        let node = rmaker.node().unwrap();
+        let reporter = node.read().unwrap().devices()[0].reporter();
+        reporter
+            .report_param("Power", ParamValue::Bool(true))
+            .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/params/local");
+        assert_eq!(
+            encoding::decode(&published[0].payload),
+            Some(json!({"Light": {"Power": true}}))
+        );
+        assert_eq!(
+            node.read().unwrap().devices()[0]
+                .param("Power")
+                .unwrap()
+                .value(),
+            ParamValue::Bool(true)
+        );
+    }
+
+    // waits for connection on a different thread, advancing the mock clock by a poll interval at a time.
+    // returns the result of waiting and the time advanced till then
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    fn wait_for_connection(
+        timeout: Duration,
+        mut connect_after: Option<Duration>,
+    ) -> (Result<(), RmakerError>, Duration) {
+        const STEP: Duration = Duration::from_millis(100);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let rmaker = Rainmaker::new_agent("node-1".to_string());
+            tx.send(rmaker.wait_until_connected(timeout)).unwrap();
+        });
+
+        let mut advanced = Duration::ZERO;
+        loop {
+            if connect_after.is_some_and(|after| advanced >= after) {
+                connect_after = None;
+                rmaker_mqtt::reconnect().unwrap();
+            }
+            if let Ok(result) = rx.recv_timeout(Duration::from_millis(20)) {
+                return (result, advanced);
+            }
+            assert!(advanced <= timeout * 2, "wait did not end after timeout");
+            clock::mock().advance(STEP);
+            advanced += STEP;
+        }
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    #[test]
+    fn wait_returns_promptly_once_connected() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        rmaker_mqtt::disconnect().unwrap();
+
+        let (result, advanced) =
+            wait_for_connection(Duration::from_secs(30), Some(Duration::from_secs(1)));
+
+        assert!(result.is_ok());
+        assert!(advanced <= Duration::from_millis(1100));
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "mock-clock"))]
+    #[test]
+    fn wait_times_out_if_not_connected() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        rmaker_mqtt::disconnect().unwrap();
+
+        let (result, advanced) = wait_for_connection(Duration::from_secs(2), None);
+
+        assert!(matches!(result, Err(RmakerError::Timeout)));
+        assert!(advanced >= Duration::from_secs(2));
+    }
+
+    #[derive(Default)]
+    struct ProvEndpoints(HashMap<String, ProvEndpointHandler>);
+
+    impl ProvEndpointRegistry for ProvEndpoints {
+        fn add_endpoint(&mut self, name: &str, handler: ProvEndpointHandler) {
+            self.0.insert(name.to_string(), handler);
+        }
+    }
+
+    #[test]
+    fn custom_prov_endpoint_handler_receives_posted_data() {
+        let mut endpoints = ProvEndpoints::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+
+        register_prov_endpoint(&mut endpoints, "custom-config", move |ep, data| {
+            received_2
+                .lock()
+                .unwrap()
+                .push((ep.to_string(), data.to_vec()));
+            b"ok".to_vec()
+        });
+
+        let handler = &endpoints.0["custom-config"];
+        assert_eq!(handler("custom-config", b"{\"mode\": 1}"), b"ok");
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![("custom-config".to_string(), b"{\"mode\": 1}".to_vec())]
+        );
+    }
+
+    // retain flag of node config published on start
+    #[cfg(feature = "mock-mqtt")]
+    fn node_config_retain_on_start(retain: bool) -> bool {
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+        rmaker.set_retain_node_config(retain);
+        rmaker.start().unwrap();
+
+        rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .find(|msg| msg.topic == "node/node-1/config")
+            .expect("node config not published")
+            .retain
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn node_config_is_retained_if_enabled() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        assert!(node_config_retain_on_start(true));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn node_config_is_not_retained_by_default() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        assert!(!node_config_retain_on_start(false));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn alert_is_published_on_alert_topic() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+
+        rmaker.raise_alert("Filter needs cleaning").unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/alert");
+        assert_eq!(
+            serde_json::from_slice::<Value>(&published[0].payload).unwrap(),
+            json!({"esp.alert.str": "Filter needs cleaning"})
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn debug_state_lists_subscriptions_made_on_start() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+
+        let state = rmaker.debug_state();
+
+        assert_eq!(state.node_id, "node-1");
+        assert_eq!(state.devices, ["Light"]);
+        assert_eq!(
+            state.subscribed_topics,
+            ["node/node-1/params/get", "node/node-1/params/remote"]
+        );
+        assert!(state.connected);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn overridden_topic_suffixes_are_used() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        topics::set_test_topic_config(TopicConfig {
+            config: "cfg".to_string(),
+            params_remote: "params/set".to_string(),
+            ..Default::default()
+        });
+
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+        rmaker.start().unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert!(published.iter().any(|m| m.topic == "node/node-1/cfg"));
+        assert!(!published.iter().any(|m| m.topic == "node/node-1/config"));
+        let subscribed = rmaker_mqtt::mock::take_subscribed();
+        assert!(subscribed.iter().any(|t| t == "node/node-1/params/set"));
+        assert!(!subscribed.iter().any(|t| t == "node/node-1/params/remote"));
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn params_get_request_publishes_current_params() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .unwrap();
+        rmaker_mqtt::mock::take_published();
+
+        assert!(rmaker_mqtt::mock::inject_message(
+            "node/node-1/params/get",
+            Vec::new()
+        ));
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/params/local");
+        assert_eq!(
+            encoding::decode(&published[0].payload),
+            Some(json!({"Light": {"Power": true}}))
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn custom_publish_is_prefixed_with_node_topic() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+
+        rmaker
+            .publish_custom("bridge/status", b"online".to_vec())
+            .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/bridge/status");
+        assert_eq!(published[0].payload, b"online");
+    }
+
+    #[test]
+    fn custom_publish_rejects_invalid_suffix() {
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+
+        for suffix in ["", "/bridge", "bridge/+", "bridge/#"] {
+            assert!(matches!(
+                rmaker.publish_custom(suffix, b"online".to_vec()),
+                Err(RmakerError::InvalidTopic(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn custom_subscription_receives_messages() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = Rainmaker::new_agent("node-1".to_string());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+
+        rmaker
+            .subscribe_custom("bridge/+/cmd", move |msg| {
+                received_2.lock().unwrap().push(msg.topic)
+            })
+            .unwrap();
+
+        assert!(rmaker_mqtt::mock::inject_message(
+            "node/node-1/bridge/lamp/cmd",
+            b"on".to_vec()
+        ));
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec!["node/node-1/bridge/lamp/cmd".to_string()]
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn reconnect_resubscribes_and_reports_current_params() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .unwrap();
+        rmaker_mqtt::mock::take_published();
+
+        rmaker_mqtt::disconnect().unwrap();
+        rmaker_mqtt::reconnect().unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "node/node-1/params/local");
+        assert_eq!(
+            encoding::decode(&published[0].payload),
+            Some(json!({"Light": {"Power": true}}))
+        );
+        assert!(rmaker_mqtt::subscribed_topics().contains(&"node/node-1/params/remote".to_string()));
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": false}}))
+            .unwrap();
+        assert_eq!(rmaker_mqtt::mock::take_published().len(), 1);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn device_added_after_start_is_published_on_update() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let rmaker = started_agent(reporting_light_node());
+        let mut fan = Device::new("Fan", DeviceType::Fan);
+        fan.add_param(Param::new_power("Power", false)).unwrap();
+
+        rmaker
+            .with_node(|node| node.add_device(fan))
+            .unwrap()
+            .unwrap();
+        rmaker.update_node_config().unwrap();
+
+        let config = rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .find(|msg| msg.topic == "node/node-1/config")
+            .expect("node config not published");
+        let config = encoding::decode(&config.payload).unwrap();
+        let devices: Vec<&str> = config["devices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|device| device["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(devices, ["Light", "Fan"]);
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn shutdown_unsubscribes_and_disconnects() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let mut rmaker = started_agent(reporting_light_node());
+        rmaker.enable_heartbeat(Duration::from_secs(30));
+        assert!(!rmaker_mqtt::subscribed_topics().is_empty());
+
+        rmaker.shutdown().unwrap();
+
+        assert!(rmaker.heartbeat.is_none());
+        assert!(rmaker.local_ctrl.is_none());
+        assert!(rmaker_mqtt::subscribed_topics().is_empty());
+        assert!(!rmaker_mqtt::is_mqtt_connected());
+        assert!(!rmaker_mqtt::mock::inject_message(
+            "node/node-1/params/remote",
+            json!({"Light": {"Power": true}}).to_string().into_bytes()
+        ));
+    }
+
+    #[cfg(all(feature = "mock-mqtt", feature = "async"))]
+    #[test]
+    fn async_start_publishes_config_and_subscribes() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut rmaker = Rainmaker::new_agent("node-1".to_string());
+        rmaker.register_node(reporting_light_node());
+
+        runtime.block_on(rmaker.start_async()).unwrap();
+
+        let topics: Vec<String> = rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .map(|msg| msg.topic)
+            .collect();
+        assert!(topics.contains(&"node/node-1/config".to_string()));
+        assert!(topics.contains(&"node/node-1/params/local/init".to_string()));
+        assert!(rmaker_mqtt::subscribed_topics().contains(&"node/node-1/params/remote".to_string()));
+
+        rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .unwrap();
+        assert_eq!(rmaker_mqtt::mock::take_published().len(), 1);
+    }
+
+    // light recording values received by its callback
+    fn recording_light_node() -> (SharedNode, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+        let mut device = Device::new("Light", DeviceType::Lightbulb);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device.register_callback(Box::new(move |params, _handle: DeviceHandle| {
+            received_2.lock().unwrap().push(json!(params));
+        }));
+        let node = NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "v1.0")
+            .device(device)
+            .build()
+            .unwrap();
+
+        (Arc::new(RwLock::new(node)), received)
+    }
+
+    fn remote_params(payload: Value) -> ReceivedMessage {
+        ReceivedMessage {
+            topic: "node/node-1/params/remote".to_string(),
+            payload: payload.to_string().into_bytes(),
+        }
+    }
+
+    #[test]
+    fn command_dropped_by_interceptor_does_not_reach_device() {
+        let (node, received) = recording_light_node();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_2 = seen.clone();
+        let interceptor: CommandInterceptor = Box::new(move |command: &RawCommand| {
+            seen_2.lock().unwrap().push(command.params.clone());
+            CommandDecision::Drop
+        });
+
+        remote_params_callback(
+            remote_params(json!({"Light": {"Power": true}})),
+            &node,
+            &RwLock::new(Some(interceptor)),
+            None,
+        );
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![json!({"Light": {"Power": true}})]
+        );
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(
+            node.read().unwrap().devices()[0]
+                .param("Power")
+                .unwrap()
+                .value(),
+            ParamValue::Bool(false)
+        );
+    }
+
+    #[test]
+    fn command_allowed_by_interceptor_reaches_device() {
+        let (node, received) = recording_light_node();
+        let interceptor: CommandInterceptor = Box::new(|_: &RawCommand| CommandDecision::Proceed);
+
+        remote_params_callback(
+            remote_params(json!({"Light": {"Power": true}})),
+            &node,
+            &RwLock::new(Some(interceptor)),
+            None,
+        );
+
+        assert_eq!(*received.lock().unwrap(), vec![json!({"Power": true})]);
+    }
+
+    #[test]
+    fn simulated_params_are_not_delivered_before_start() {
+        let rmaker = Rainmaker::new_agent("node-not-started".to_string());
+
+        assert!(rmaker
+            .simulate_remote_params(json!({"Light": {"Power": true}}))
+            .is_err());
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn node_config_and_initial_params_are_published() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let node = light_node("v1.0");
+
+        initial_messages(
+            &node,
+            "node-1",
+            false,
+            true,
+            (MQTT_MESSAGE_SIZE_LIMIT, ConfigSizePolicy::Reject),
+        )
+        .unwrap()
+        .publish()
+        .unwrap();
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].topic, "node/node-1/config");
+        assert!(!published[0].retain);
+        let node_config = encoding::decode(&published[0].payload).unwrap();
+        assert_eq!(node_config["node_id"], "node-1");
+        assert_eq!(node_config["config_version"], NODE_CONFIG_VERSION);
+        assert_eq!(node_config["info"]["fw_version"], "v1.0");
+        assert_eq!(node_config["devices"][0]["name"], "Light");
+        assert_eq!(node_config["devices"][0]["type"], "esp.device.lightbulb");
+        assert_eq!(node_config["devices"][0]["params"][0]["name"], "Power");
+        assert_eq!(node_config["devices"][0]["params"][1]["name"], "Brightness");
+
+        assert_eq!(published[1].topic, "node/node-1/params/local/init");
+        assert_eq!(
+            encoding::decode(&published[1].payload),
+            Some(json!({"Light": {"Power": false, "Brightness": 50}}))
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn unchanged_node_config_is_not_published() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let node = light_node("v1.0");
+
+        let mut messages = initial_messages(
+            &node,
+            "node-1",
+            false,
+            false,
+            (MQTT_MESSAGE_SIZE_LIMIT, ConfigSizePolicy::Reject),
+        )
+        .unwrap();
+        // as if hash of the same configuration was stored on previous start
+        messages.node_config_message = None;
+        messages.publish().unwrap();
+
+        let topics: Vec<String> = rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .map(|msg| msg.topic)
+            .collect();
+        assert_eq!(topics, vec!["node/node-1/params/local/init"]);
+    }
+
+    // agent is a process wide singleton, hence this is the only test initializing it
+    #[test]
+    fn get_returns_initialized_agent() {
+        assert!(unsafe { Rainmaker::get() }.is_none());
+
+        let initialized: *const Rainmaker = Rainmaker::set_agent("node-1".to_string()).unwrap();
+        let agent: *const Rainmaker = unsafe { Rainmaker::get() }.unwrap();
+
+        assert_eq!(agent, initialized);
+        assert_eq!(unsafe { Rainmaker::get() }.unwrap().get_node_id(), "node-1");
+    }
+
+    #[test]
+    fn node_config_has_config_version() {
+        let (node_config, _) = hashed_node_config(&light_node("v1.0")).unwrap();
+
+        assert_eq!(node_config["config_version"], NODE_CONFIG_VERSION);
+    }
+
+    #[test]
+    fn unchanged_config_is_not_republished() {
+        // node is created again on every boot
+        let (_, published_hash) = hashed_node_config(&light_node("v1.0")).unwrap();
+        let (_, config_hash) = hashed_node_config(&light_node("v1.0")).unwrap();
+
+        assert_eq!(config_hash, published_hash);
+        assert!(!config_changed(config_hash, Some(published_hash)));
+    }
+
+    #[test]
+    fn changed_config_is_republished() {
+        let (_, published_hash) = hashed_node_config(&light_node("v1.0")).unwrap();
+        let (_, config_hash) = hashed_node_config(&light_node("v1.1")).unwrap();
+
+        assert!(config_changed(config_hash, Some(published_hash)));
+    }
+
+    #[test]
+    fn config_is_published_if_hash_not_stored() {
+        let (_, config_hash) = hashed_node_config(&light_node("v1.0")).unwrap();
+
+        assert!(config_changed(config_hash, None));
+    }
+
+    #[test]
+    fn config_patch_contains_only_changed_bounds() {
+        let (published_config, _) = hashed_node_config(&light_node("v1.0")).unwrap();
+        let mut brightness = Param::new_brightness("Brightness", 50);
+        brightness.add_bounds(0, 100, 5);
+        let node = light_node_with_brightness("v1.0", brightness);
+        let (node_config, _) = hashed_node_config(&node).unwrap();
+
+        assert_eq!(
+            utils::json_patch(&published_config, &node_config),
+            vec![json!({
+                "op": "replace",
+                "path": "/devices/0/params/1/bounds/step",
+                "value": 5
+            })]
+        );
+    }
+
+    fn recording_prov_event_cb() -> (
+        Arc<RwLock<Option<ProvEventCb>>>,
+        Arc<Mutex<Vec<ProvisioningEvent>>>,
+    ) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_2 = events.clone();
+        let cb: ProvEventCb = Box::new(move |event| events_2.lock().unwrap().push(event));
+        (Arc::new(RwLock::new(Some(cb))), events)
+    }
+
+    fn user_mapping_request(user_id: &str, secret_key: &str) -> Vec<u8> {
+        let req = RMakerConfigPayload {
+            msg: RMakerConfigMsgType::TypeCmdSetUserMapping,
+            payload: mod_RMakerConfigPayload::OneOfpayload::cmd_set_user_mapping(
+                CmdSetUserMapping {
+                    UserID: user_id.to_string(),
+                    SecretKey: secret_key.to_string(),
+                },
+            ),
+        };
+        let mut out = vec![];
+        req.write_message(&mut Writer::new(&mut out)).unwrap();
+        out
+    }
+
+    fn response_status(response: &[u8]) -> RMakerConfigStatus {
+        match RMakerConfigPayload::try_from(response).unwrap().payload {
+            mod_RMakerConfigPayload::OneOfpayload::resp_set_user_mapping(resp) => resp.Status,
+            _ => panic!("not a user mapping response"),
+        }
+    }
+
+    #[test]
+    fn prov_event_is_delivered_to_callback() {
+        let (prov_event_cb, events) = recording_prov_event_cb();
+
+        emit_prov_event(&prov_event_cb, ProvisioningEvent::CredsReceived);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ProvisioningEvent::CredsReceived]
+        );
+    }
+
+    #[test]
+    fn prov_event_without_callback_is_ignored() {
+        emit_prov_event(&RwLock::new(None), ProvisioningEvent::Started);
+    }
+
+    #[test]
+    fn malformed_user_mapping_emits_failed_event() {
+        let (prov_event_cb, events) = recording_prov_event_cb();
+
+        let response =
+            cloud_user_assoc_callback("cloud_user_assoc", &[0xff], "node-1", &prov_event_cb);
+
+        assert_eq!(response_status(&response), RMakerConfigStatus::InvalidParam);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ProvisioningEvent::Failed(
+                "malformed user mapping request".to_string()
+            )]
+        );
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn published_user_mapping_emits_cloud_assoc_done() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let (prov_event_cb, events) = recording_prov_event_cb();
+
+        let response = cloud_user_assoc_callback(
+            "cloud_user_assoc",
+            &user_mapping_request("user-1", "secret-1"),
+            "node-1",
+            &prov_event_cb,
+        );
+
+        assert_eq!(response_status(&response), RMakerConfigStatus::Success);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ProvisioningEvent::CloudAssocDone]
+        );
+
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        let mapping: Value = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(mapping["user_id"], "user-1");
+        assert_eq!(mapping["secret_key"], "secret-1");
+    }
+
+    // recreated on every run, so that files of previous runs do not leak into tests
+    #[cfg(target_os = "linux")]
+    fn claimdata_folder(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("rmaker-claimdata-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), content).unwrap();
+        }
+        path
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn missing_claimdata_folder_is_reported_missing() {
+        let path = env::temp_dir().join("rmaker-claimdata-does-not-exist");
+
+        assert!(matches!(
+            read_claimdata_folder(&path, None),
+            Err(RmakerError::ClaimDataMissing)
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn claimdata_folder_without_files_is_invalid() {
+        let path = claimdata_folder("empty", &[]);
+
+        assert!(matches!(
+            read_claimdata_folder(&path, None),
+            Err(RmakerError::ClaimDataInvalid)
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn claimdata_folder_without_random_is_invalid() {
+        let path = claimdata_folder(
+            "no-random",
+            &[
+                ("node.info", "node-1"),
+                ("node.crt", "cert"),
+                ("node.key", "key"),
+            ],
+        );
+
+        assert!(matches!(
+            read_claimdata_folder(&path, None),
+            Err(RmakerError::ClaimDataInvalid)
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn claimdata_folder_is_read() {
+        let path = claimdata_folder(
+            "complete",
+            &[
+                ("node.info", "node-1"),
+                ("node.crt", "cert"),
+                ("node.key", "key"),
+                ("random.info", "random"),
+            ],
+        );
+
+        let claim_data = read_claimdata_folder(&path, None).unwrap();
+
+        assert_eq!(claim_data.node_id, "node-1");
+        assert_eq!(claim_data.cert, "cert");
+        assert_eq!(claim_data.key, "key");
+        assert_eq!(claim_data.random, "random");
+    }
+
+    #[cfg(all(target_os = "linux", feature = "pkcs12"))]
+    fn pkcs12_claimdata_folder(name: &str, password: &str) -> std::path::PathBuf {
+        let bundle = p12::PFX::new(b"node cert", b"node key", None, password, "node-1").unwrap();
+        let path = claimdata_folder(name, &[("node.info", "node-1"), ("random.info", "random")]);
+        fs::write(path.join("node.p12"), bundle.to_der()).unwrap();
+        path
+    }
+
+    #[cfg(all(target_os = "linux", feature = "pkcs12"))]
+    #[test]
+    fn credentials_are_extracted_from_pkcs12_bundle() {
+        let path = pkcs12_claimdata_folder("p12", "secret");
+        env::set_var("RMAKER_CLAIMDATA_P12_PASSWORD_TEST_P12", "secret");
+
+        let claim_data = read_claimdata_folder(&path, Some("test_p12")).unwrap();
+
+        assert_eq!(claim_data.node_id, "node-1");
+        assert_eq!(
+            claim_data.cert,
+            utils::pem_encode("CERTIFICATE", b"node cert")
+        );
+        assert_eq!(
+            claim_data.key,
+            utils::pem_encode("PRIVATE KEY", b"node key")
+        );
+        assert_eq!(claim_data.random, "random");
+    }
+
+    #[cfg(all(target_os = "linux", feature = "pkcs12"))]
+    #[test]
+    fn pkcs12_bundle_with_wrong_password_is_invalid() {
+        let path = pkcs12_claimdata_folder("p12-wrong-password", "secret");
+        env::set_var("RMAKER_CLAIMDATA_P12_PASSWORD_TEST_P12_WRONG", "guess");
+
+        assert!(matches!(
+            read_claimdata_folder(&path, Some("test_p12_wrong")),
+            Err(RmakerError::ClaimDataInvalid)
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn claimdata_of_selected_profile_is_read() {
+        for profile in ["test_staging", "test_prod"] {
+            let path = claimdata_folder(
+                profile,
+                &[
+                    ("node.info", &format!("node-{}", profile)),
+                    ("node.crt", "cert"),
+                    ("node.key", "key"),
+                    ("random.info", "random"),
+                ],
+            );
+            env::set_var(
+                format!("RMAKER_CLAIMDATA_PATH_{}", profile.to_uppercase()),
+                path,
+            );
+        }
+
+        let claim_data = profile_claimdata(Some("test_staging")).unwrap();
+        assert_eq!(claim_data.node_id, "node-test_staging");
+
+        let claim_data = profile_claimdata(Some("test_prod")).unwrap();
+        assert_eq!(claim_data.node_id, "node-test_prod");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn claimdata_of_unknown_profile_is_missing() {
+        assert!(matches!(
+            profile_claimdata(Some("test_unknown")),
+            Err(RmakerError::ClaimDataMissing)
+        ));
+    }
 }
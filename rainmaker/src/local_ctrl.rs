@@ -3,43 +3,80 @@ use serde_json::Value;
 use std::{collections::HashMap, sync::Arc};
 
 #[cfg(target_os = "linux")]
-use std::process::{Child, Command};
+use std::{
+    process::{Child, Command},
+    thread,
+    time::Duration,
+};
 
-use crate::node::Node;
+use crate::node::SharedNode;
 
 const LOCAL_CTRL_TYPE_NODECONFIG: u32 = 1;
 const LOCAL_CTRL_TYPE_PARAM: u32 = 2;
+pub(crate) const LOCAL_CTRL_TYPE_CUSTOM: u32 = 3;
 
 const LOCAL_CTRL_FLAG_READONLY: u32 = 1;
 
+// instance names tried on linux are <name>, <name>-2, ..., <name>-<MDNS_INSTANCE_ATTEMPTS>
+#[cfg(target_os = "linux")]
+const MDNS_INSTANCE_ATTEMPTS: u32 = 5;
+// avahi-publish exits shortly after start if service can not be registered
+#[cfg(target_os = "linux")]
+const MDNS_PUBLISH_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+pub(crate) type LocalCtrlGetter = Arc<dyn Fn() -> Vec<u8> + Send + Sync + 'static>;
+pub(crate) type LocalCtrlSetter = Arc<dyn Fn(&[u8]) + Send + Sync + 'static>;
+
+// property exposed over local control, routed to its handlers by name
+#[derive(Clone)]
+pub(crate) struct LocalCtrlProperty {
+    pub(crate) prop_type: u32,
+    pub(crate) get: LocalCtrlGetter,
+    // None for read-only properties
+    pub(crate) set: Option<LocalCtrlSetter>,
+}
+
 pub struct RmakerLocalCtrl {
     // not used once initialized, but don't want it to be dropped
     _local_ctrl: LocalControl,
     #[cfg(target_os = "linux")]
-    child: Child,
+    _mdns: MdnsAdvertisement,
 }
 
+// avahi-publish process advertising local control, stopped once dropped along with local control
+#[cfg(target_os = "linux")]
+struct MdnsAdvertisement(Child);
+
 impl RmakerLocalCtrl {
-    pub fn new(node: Arc<Node>, node_id: &str) -> Result<RmakerLocalCtrl, ()> {
-        let node_2 = node.clone();
+    pub fn new(
+        node: SharedNode,
+        node_id: &str,
+        instance_name: Option<&str>,
+        custom_properties: &[(String, LocalCtrlProperty)],
+    ) -> Result<RmakerLocalCtrl, ()> {
+        let properties = Arc::new(all_properties(node, custom_properties));
+        let properties_2 = properties.clone();
         let mut local_ctrl = LocalControl::new(
-            Box::new(move |name, type_, flags| local_ctrl_get_val(name, type_, flags, &node)),
+            Box::new(move |name, type_, flags| local_ctrl_get_val(name, type_, flags, &properties)),
             Box::new(move |name, type_, flags, data| {
-                local_ctrl_set_val(name, type_, flags, data, &node_2)
+                local_ctrl_set_val(name, type_, flags, data, &properties_2)
             }),
         );
-        local_ctrl.add_property(
-            "config".to_string(),
-            LOCAL_CTRL_TYPE_NODECONFIG,
-            LOCAL_CTRL_FLAG_READONLY,
-        );
-        local_ctrl.add_property("params".to_string(), LOCAL_CTRL_TYPE_PARAM, 0);
+        for (name, property) in properties_2.iter() {
+            let flags = match property.set {
+                Some(_) => 0,
+                None => LOCAL_CTRL_FLAG_READONLY,
+            };
+            local_ctrl.add_property(name.clone(), property.prop_type, flags);
+        }
+
+        let instance_name = instance_name.unwrap_or(node_id);
 
         #[cfg(target_os = "espidf")]
-        let ret = advertise_mdns_esp(node_id);
+        let ret = advertise_mdns_esp(node_id, instance_name);
 
         #[cfg(target_os = "linux")]
-        let ret = advertise_mdns_linux(node_id);
+        let ret = advertise_mdns_linux(node_id, instance_name);
 
         if ret.is_err() {
             return Err(());
@@ -48,17 +85,15 @@ impl RmakerLocalCtrl {
         Ok(RmakerLocalCtrl {
             _local_ctrl: local_ctrl,
             #[cfg(target_os="linux")]
-            child: ret.unwrap(),
+            _mdns: MdnsAdvertisement(ret.unwrap()),
         })
     }
 }
 
 #[cfg(target_os = "linux")]
-impl Drop for RmakerLocalCtrl{
+impl Drop for MdnsAdvertisement{
     fn drop(&mut self) {
-        if self.child.kill().is_err(){
-            log::error!("Failed to stop mDNS advertisement");
-        };
+        stop_mdns_linux(&mut self.0);
     }
 }
 
@@ -73,11 +108,51 @@ impl Drop for RmakerLocalCtrl{
 
 
 #[cfg(target_os = "linux")]
-fn advertise_mdns_linux(node_id: &str) -> Result<Child, ()>{
+fn stop_mdns_linux(child: &mut Child) {
+    if child.kill().is_err(){
+        log::error!("Failed to stop mDNS advertisement");
+    };
+    // reap the child so that it does not linger as zombie when local control is restarted
+    let _ = child.wait();
+}
+
+#[cfg(target_os = "linux")]
+fn advertise_mdns_linux(node_id: &str, instance_name: &str) -> Result<Child, ()> {
+    for attempt in 1..=MDNS_INSTANCE_ATTEMPTS {
+        let instance = mdns_instance(instance_name, attempt);
+        let mut child = avahi_publish_command(node_id, &instance)
+            .spawn()
+            .map_err(|_| ())?;
+
+        // service is published as long as avahi-publish keeps running
+        thread::sleep(MDNS_PUBLISH_SETTLE_TIME);
+        match child.try_wait() {
+            Ok(None) => return Ok(child),
+            _ => log::warn!(
+                "Could not publish mDNS service {}, possibly a name collision",
+                instance
+            ),
+        }
+    }
+
+    Err(())
+}
+
+// instance name tried on the attempt, starting from 1
+#[cfg(target_os = "linux")]
+fn mdns_instance(instance_name: &str, attempt: u32) -> String {
+    match attempt {
+        1 => instance_name.to_string(),
+        _ => format!("{}-{}", instance_name, attempt),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn avahi_publish_command(node_id: &str, instance_name: &str) -> Command {
     let mut command = Command::new("avahi-publish");
     command.args([
         "--service",
-        &format!("{}", node_id),
+        instance_name,
         "_esp_local_ctrl._tcp",
         "8080",
         "version_endpoint=\"/esp_local_ctrl/version\"",
@@ -85,13 +160,11 @@ fn advertise_mdns_linux(node_id: &str) -> Result<Child, ()>{
         "control_endpoint=\"/esp_local_ctrl/control\"",
         &format!("node_id={}", node_id),
     ]);
-
-    // TODO: validate if service is actually published
-    command.spawn().map_err(|_| ())
+    command
 }
 
 #[cfg(target_os = "espidf")]
-fn advertise_mdns_esp(node_id: &str) -> Result<(), ()> {
+fn advertise_mdns_esp(node_id: &str, instance_name: &str) -> Result<(), ()> {
     use esp_idf_svc::sys::{
         mdns::{mdns_free, mdns_hostname_set, mdns_init, mdns_service_add, mdns_txt_item_t},
         ESP_OK,
@@ -109,6 +182,7 @@ fn advertise_mdns_esp(node_id: &str) -> Result<(), ()> {
 
     let node_id_key = CString::new("node_id").unwrap();
     let node_id_value = CString::new(node_id).unwrap();
+    let instance_name = CString::new(instance_name).unwrap();
 
     let mut records = [
         mdns_txt_item_t {
@@ -140,7 +214,7 @@ fn advertise_mdns_esp(node_id: &str) -> Result<(), ()> {
         };
 
         if mdns_service_add(
-            node_id_value.as_ptr(),
+            instance_name.as_ptr(),
             CString::new("_esp_local_ctrl").unwrap().as_ptr(),
             CString::new("_tcp").unwrap().as_ptr(),
             8080,
@@ -156,38 +230,224 @@ fn advertise_mdns_esp(node_id: &str) -> Result<(), ()> {
     Ok(())
 }
 
-fn local_ctrl_get_val(name: &str, _prop_type: u32, _flags: u32, node: &Arc<Node>) -> Vec<u8> {
-    let res = match name {
-        "config" => serde_json::to_vec(node.as_ref()).unwrap(),
-        "params" => {
-            let params = node.get_param_values();
-            serde_json::to_vec(&params).unwrap()
-        }
-        _ => {
-            log::error!("Trying to set unknown proprty {}", name);
-            return Default::default();
+// standard properties followed by custom ones, which can not replace standard properties
+fn all_properties(
+    node: SharedNode,
+    custom_properties: &[(String, LocalCtrlProperty)],
+) -> HashMap<String, LocalCtrlProperty> {
+    let mut properties = node_properties(node);
+    for (name, property) in custom_properties {
+        if properties.contains_key(name) {
+            log::error!(
+                "Ignoring local control property {} which already exists",
+                name
+            );
+            continue;
         }
-    };
+        properties.insert(name.clone(), property.clone());
+    }
+
+    properties
+}
+
+// standard properties for node configuration and param values
+fn node_properties(node: SharedNode) -> HashMap<String, LocalCtrlProperty> {
+    let node_2 = node.clone();
+    let node_3 = node.clone();
 
-    res
+    HashMap::from([
+        (
+            "config".to_string(),
+            LocalCtrlProperty {
+                prop_type: LOCAL_CTRL_TYPE_NODECONFIG,
+                get: Arc::new(move || serde_json::to_vec(&*node.read().unwrap()).unwrap()),
+                set: None,
+            },
+        ),
+        (
+            "params".to_string(),
+            LocalCtrlProperty {
+                prop_type: LOCAL_CTRL_TYPE_PARAM,
+                get: Arc::new(move || match node_2.read().unwrap().get_param_values() {
+                    Ok(params) => serde_json::to_vec(&params).unwrap(),
+                    Err(err) => {
+                        log::error!("Failed to get param values: {}", err);
+                        Default::default()
+                    }
+                }),
+                set: Some(Arc::new(move |data| set_params(data, &node_3))),
+            },
+        ),
+    ])
 }
 
-fn local_ctrl_set_val(name: &str, _prop_type: u32, flags: u32, data: Vec<u8>, node: &Arc<Node>) {
-    if flags == LOCAL_CTRL_FLAG_READONLY {
-        log::error!("Trying to modify read only property");
-        return;
+// values are validated the same way as remote params
+fn set_params(data: &[u8], node: &SharedNode) {
+    let dispatched = serde_json::from_slice::<Value>(data)
+        .and_then(|params| node.read().unwrap().dispatch_params(params));
+
+    if dispatched.is_err() {
+        log::error!(
+            "Ignoring malformed params from local control: {}",
+            String::from_utf8_lossy(data)
+        );
     }
-    match name {
-        "params" => {
-            // TODO: Make appropriate changes to use &str instead of String for parameter name
-            let params: HashMap<&str, HashMap<String, Value>> =
-                serde_json::from_slice(&data).unwrap();
-            for (device, params) in params {
-                node.exeute_device_callback(device, params);
-            }
-        }
-        _ => {
-            log::error!("Trying to set unknown property: {}", name);
+    crate::republish_changed_node_config(node);
+}
+
+fn local_ctrl_get_val(
+    name: &str,
+    _prop_type: u32,
+    _flags: u32,
+    properties: &HashMap<String, LocalCtrlProperty>,
+) -> Vec<u8> {
+    match properties.get(name) {
+        Some(property) => (property.get)(),
+        None => {
+            log::error!("Trying to get unknown property {}", name);
+            Default::default()
         }
     }
 }
+
+fn local_ctrl_set_val(
+    name: &str,
+    _prop_type: u32,
+    _flags: u32,
+    data: Vec<u8>,
+    properties: &HashMap<String, LocalCtrlProperty>,
+) {
+    match properties.get(name) {
+        Some(LocalCtrlProperty { set: Some(set), .. }) => set(&data),
+        Some(_) => log::error!("Trying to modify read only property {}", name),
+        None => log::error!("Trying to set unknown property: {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, RwLock};
+
+    use crate::node::Node;
+
+    // read-write property recording the values set
+    fn recording_property(value: &'static [u8]) -> (LocalCtrlProperty, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let set_values = Arc::new(Mutex::new(Vec::new()));
+        let set_values_2 = set_values.clone();
+        let property = LocalCtrlProperty {
+            prop_type: LOCAL_CTRL_TYPE_CUSTOM,
+            get: Arc::new(move || value.to_vec()),
+            set: Some(Arc::new(move |data: &[u8]| {
+                set_values_2.lock().unwrap().push(data.to_vec())
+            })),
+        };
+
+        (property, set_values)
+    }
+
+    fn shared_node() -> SharedNode {
+        Arc::new(RwLock::new(Node::new("node-1".to_string())))
+    }
+
+    #[test]
+    fn custom_property_get_and_set_are_routed_to_handlers() {
+        let (ota_status, set_values) = recording_property(b"idle");
+        let properties = all_properties(shared_node(), &[("ota_status".to_string(), ota_status)]);
+
+        assert_eq!(
+            local_ctrl_get_val("ota_status", LOCAL_CTRL_TYPE_CUSTOM, 0, &properties),
+            b"idle"
+        );
+        local_ctrl_set_val(
+            "ota_status",
+            LOCAL_CTRL_TYPE_CUSTOM,
+            0,
+            b"start".to_vec(),
+            &properties,
+        );
+
+        assert_eq!(*set_values.lock().unwrap(), vec![b"start".to_vec()]);
+    }
+
+    #[test]
+    fn custom_property_does_not_replace_standard_one() {
+        let (config, _) = recording_property(b"custom");
+        let properties = all_properties(shared_node(), &[("config".to_string(), config)]);
+
+        assert_eq!(properties["config"].prop_type, LOCAL_CTRL_TYPE_NODECONFIG);
+        assert_eq!(properties.len(), 2);
+    }
+
+    #[test]
+    fn read_only_custom_property_is_not_set() {
+        let (time, set_values) = recording_property(b"12:00");
+        let time = LocalCtrlProperty { set: None, ..time };
+        let properties = all_properties(shared_node(), &[("time".to_string(), time)]);
+
+        local_ctrl_set_val(
+            "time",
+            LOCAL_CTRL_TYPE_CUSTOM,
+            0,
+            b"13:00".to_vec(),
+            &properties,
+        );
+
+        assert_eq!(
+            local_ctrl_get_val("time", LOCAL_CTRL_TYPE_CUSTOM, 0, &properties),
+            b"12:00"
+        );
+        assert!(set_values.lock().unwrap().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stopping_mdns_kills_advertisement() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+
+        stop_mdns_linux(&mut child);
+
+        let status = child
+            .try_wait()
+            .unwrap()
+            .expect("advertisement still running");
+        assert_eq!(status.signal(), Some(9));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn dropping_advertisement_terminates_avahi_process() {
+        let child = Command::new("sleep").arg("60").spawn().unwrap();
+        let process = format!("/proc/{}", child.id());
+
+        drop(MdnsAdvertisement(child));
+
+        assert!(!std::path::Path::new(&process).exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn configured_instance_name_is_passed_to_avahi() {
+        let command = avahi_publish_command("node-1", "Kitchen Hub");
+
+        assert_eq!(command.get_program(), "avahi-publish");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args[..3],
+            ["--service", "Kitchen Hub", "_esp_local_ctrl._tcp"]
+        );
+        assert_eq!(args.last().unwrap(), &"node_id=node-1");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn colliding_instance_names_get_a_suffix() {
+        let instances: Vec<_> = (1..=3)
+            .map(|attempt| mdns_instance("node-1", attempt))
+            .collect();
+
+        assert_eq!(instances, ["node-1", "node-1-2", "node-1-3"]);
+    }
+}
@@ -13,6 +13,9 @@ Information (info, Object)
     Name (name, String)
     FW Version (fw_version, String)
     Type (type, String)
+    Model (model, String)
+    Project Name (project_name, String)
+    Platform (platform, String)
 Node Attributes (attributes, Array of Objects)
     Name (name, String)
     Value (value, String)
@@ -35,29 +38,98 @@ Devices (devices, Array of objects)
             Step (step, Number)
 */
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
 
 use serde::Serialize;
 use serde_json::Value;
 
 use crate::device::Device;
+use crate::error::RmakerNodeError;
+use crate::param::ParamValue;
+use crate::utils::{self, Attributes};
 #[allow(unused)]
 use crate::Rainmaker;
 
-#[derive(Debug, Clone, Serialize)]
+// node shared between agent, cloud callbacks and local control. can be modified after agent is started
+pub(crate) type SharedNode = Arc<RwLock<Node>>;
+
+/// Observer of param changes across all devices, invoked with (device name, param name, value).
+pub type ParamObserver = dyn Fn(&str, &str, &ParamValue) + Send + Sync + 'static;
+
+/// Node information shown in RainMaker dashboards.
+///
+/// Optional fields are omitted from node configuration if not set.
+/// ```rust
+/// let info = Info {
+///     name: "Example Node".to_string(),
+///     fw_version: "v1.0".to_string(),
+///     model: Some("EX-100".to_string()),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Info {
     pub name: String,
     pub fw_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// Builder for [Node].
+///
+/// Validates the node configuration when [`build`](NodeBuilder::build) is called.
+/// ```rust
+/// let node = NodeBuilder::new(rmaker.get_node_id().to_string())
+///     .info("Example Node", "v1.0")
+///     .device(device)
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct NodeBuilder {
+    node_id: String,
+    info: Option<Info>,
+    attributes: Attributes,
+    devices: Vec<Device>,
+}
+
+#[derive(Serialize)]
 pub struct Node {
     node_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     info: Option<Info>,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
-    attributes: HashMap<String, String>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "utils::serialize_attributes"
+    )]
+    attributes: Attributes,
     devices: Vec<Device>,
+    #[serde(skip_serializing)]
+    param_observer: Option<Box<ParamObserver>>,
+    // set when a device is renamed while handling received params
+    #[serde(skip_serializing)]
+    config_changed: AtomicBool,
+}
+
+impl Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Node")
+            .field("node_id", &self.node_id)
+            .field("info", &self.info)
+            .field("attributes", &self.attributes)
+            .field("devices", &self.devices)
+            .finish()
+    }
 }
 
 impl Node {
@@ -73,8 +145,10 @@ impl Node {
         Self {
             node_id,
             info: None,
-            attributes: HashMap::new(),
+            attributes: Vec::new(),
             devices: Vec::new(),
+            param_observer: None,
+            config_changed: AtomicBool::new(false),
         }
     }
 
@@ -82,51 +156,604 @@ impl Node {
     /// ```rust
     /// node.set_info(Info{
     ///     name: "Example Node".to_string(),
-    ///     fw_version: "v1.0".to_string()
-    /// });
+    ///     fw_version: "v1.0".to_string(),
+    ///     ..Default::default()
+    /// })?;
     /// ```
-    pub fn set_info(&mut self, info: Info) {
+    ///
+    /// Returns an error if firmware version is empty.
+    pub fn set_info(&mut self, info: Info) -> Result<(), RmakerNodeError> {
+        if info.fw_version.is_empty() {
+            return Err(RmakerNodeError::EmptyFwVersion);
+        }
+
         self.info = Some(info);
+        Ok(())
     }
 
-    /// Used to define attributes of node.
+    /// Used to define attributes of node. Attributes are reported in the order they are set.
+    ///
+    /// Value of an existing attribute with the same name is replaced.
     pub fn set_attribute(&mut self, name: String, value: String) {
-        self.attributes
-            .insert(name, value)
-            .expect("Failed to set atttribute");
+        utils::set_attribute(&mut self.attributes, name, value);
     }
 
     /// Multiple devices can be associated with the node by using this method. Instance of device should be passed as an argument.
     ///
     /// Ensure that instance of [device] is created properly and callback is set appropriately in order to report updated parameter values.
     /// ```rust
-    /// node.add_device(device);
+    /// node.add_device(device)?;
     /// ```
     ///
+    /// Returns an error if a device with the same name is already added to the node.
+    ///
     /// [device]: crate::device
-    pub fn add_device(&mut self, device: Device) {
+    pub fn add_device(&mut self, device: Device) -> Result<(), RmakerNodeError> {
+        if self.devices.iter().any(|d| d.name() == device.name()) {
+            return Err(RmakerNodeError::DuplicateDevice(device.name().to_string()));
+        }
+
+        device.set_node_id(&self.node_id);
         self.devices.push(device);
+        Ok(())
     }
 
-    pub(crate) fn get_param_values(&self) -> HashMap<&str, HashMap<&str, Value>> {
+    /// Sets an observer which is invoked for every change of param value accepted from RainMaker cloud or local control,
+    /// across all devices, e.g. for logging or analytics.
+    ///
+    /// Observer is invoked before the callback of respective device.
+    /// ```rust
+    /// node.set_param_observer(Box::new(|device, param, value| {
+    ///     log::info!("{}.{} changed to {:?}", device, param, value);
+    /// }));
+    /// ```
+    pub fn set_param_observer(&mut self, observer: Box<ParamObserver>) {
+        self.param_observer = Some(observer);
+    }
+
+    /// Returns devices associated with the node, in the order of node configuration.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Removes the device with provided name from the node and returns it.
+    pub fn remove_device(&mut self, device_name: &str) -> Option<Device> {
+        let index = self.devices.iter().position(|d| d.name() == device_name)?;
+        Some(self.devices.remove(index))
+    }
+
+    /// Moves the named devices to the front of node configuration in the provided order. Phone apps list devices in this order.
+    ///
+    /// Devices which are not named retain their relative order after the named ones.
+    /// ```rust
+    /// node.set_device_order(&["Light", "Fan"])?;
+    /// ```
+    ///
+    /// Returns an error if any of the devices is not added to the node, in which case the order is not modified.
+    pub fn set_device_order(&mut self, device_names: &[&str]) -> Result<(), RmakerNodeError> {
+        if let Some(name) = device_names
+            .iter()
+            .find(|name| !self.devices.iter().any(|d| d.name() == **name))
+        {
+            return Err(RmakerNodeError::DeviceNotFound(name.to_string()));
+        }
+
+        // stable sort keeps insertion order of devices which are not named
+        self.devices.sort_by_key(|d| {
+            device_names
+                .iter()
+                .position(|name| *name == d.name())
+                .unwrap_or(device_names.len())
+        });
+
+        Ok(())
+    }
+
+    // returns an error if value of any param can not be represented in JSON
+    pub(crate) fn get_param_values(
+        &self,
+    ) -> Result<HashMap<&str, HashMap<&str, Value>>, RmakerNodeError> {
         let mut params = HashMap::<&str, HashMap<&str, Value>>::new();
         for dev in &self.devices {
             let mut curr_params = HashMap::<&str, Value>::new();
-            for p in dev.params() {
-                curr_params.insert(p.name(), p.value().clone().into());
+            for p in dev.params().iter().filter(|p| !p.is_momentary()) {
+                let value = Value::from(p.value());
+                if value.is_null() {
+                    return Err(RmakerNodeError::InvalidParamValue(format!(
+                        "{}.{}",
+                        dev.name(),
+                        p.name()
+                    )));
+                }
+                curr_params.insert(p.name(), value);
             }
             params.insert(dev.name(), curr_params);
         }
 
-        params
+        Ok(params)
     }
 
-    pub(crate) fn exeute_device_callback(&self, device_name: &str, params: HashMap<String, Value>) {
-        for device in self.devices.iter() {
-            if device.name() == device_name {
-                device.execute_callback(params);
-                break;
+    // dispatches received values({"device": {"param": value}}) to devices, which validate them against their params.
+    // returns names of unknown devices, or an error if values are not in the expected format
+    pub(crate) fn dispatch_params(&self, params: Value) -> Result<Vec<String>, serde_json::Error> {
+        let received_val: HashMap<String, HashMap<String, Value>> = serde_json::from_value(params)?;
+        let mut unknown_devices = Vec::new();
+        for (device, params) in received_val {
+            if !self.exeute_device_callback(&device, params) {
+                unknown_devices.push(device);
             }
         }
+
+        Ok(unknown_devices)
+    }
+
+    // returns true if node configuration changed since the last call, e.g. a device is renamed from phone apps
+    pub(crate) fn take_config_changed(&self) -> bool {
+        self.config_changed.swap(false, Ordering::SeqCst)
+    }
+
+    // returns false if device is not found
+    pub(crate) fn exeute_device_callback(
+        &self,
+        device_name: &str,
+        params: HashMap<String, Value>,
+    ) -> bool {
+        match self.devices.iter().find(|d| d.name() == device_name) {
+            Some(device) => {
+                if device.execute_callback(params, self.param_observer.as_deref()) {
+                    self.config_changed.store(true, Ordering::SeqCst);
+                }
+                true
+            }
+            None => {
+                // a match differing in case is likely a mistake in the command, rather than a device which is not registered
+                match self.similar_device(device_name) {
+                    Some(similar) => log::warn!(
+                        "ignoring params for unknown device {}. did you mean {}?",
+                        device_name,
+                        similar
+                    ),
+                    None => log::warn!(
+                        "ignoring params for unknown device {}. device is not registered",
+                        device_name
+                    ),
+                }
+                false
+            }
+        }
+    }
+
+    // device whose name differs only in case
+    fn similar_device(&self, device_name: &str) -> Option<&str> {
+        self.devices
+            .iter()
+            .map(|d| d.name())
+            .find(|name| name.eq_ignore_ascii_case(device_name))
+    }
+}
+
+impl NodeBuilder {
+    /// Creates a new builder for node with the provided Node ID.
+    pub fn new(node_id: String) -> Self {
+        Self {
+            node_id,
+            info: None,
+            attributes: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+
+    /// Sets node information (Name, FW Version).
+    pub fn info(mut self, name: &str, fw_version: &str) -> Self {
+        self.info = Some(Info {
+            name: name.to_string(),
+            fw_version: fw_version.to_string(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Sets node information including the optional fields.
+    pub fn node_info(mut self, info: Info) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Adds a device to the node.
+    pub fn device(mut self, device: Device) -> Self {
+        self.devices.push(device);
+        self
+    }
+
+    /// Adds an attribute to the node.
+    pub fn attribute(mut self, name: String, value: String) -> Self {
+        utils::set_attribute(&mut self.attributes, name, value);
+        self
+    }
+
+    /// Validates the configuration and creates the [Node].
+    ///
+    /// Returns an error if node id is empty, node information is not set, firmware version is empty,
+    /// no devices are added or multiple devices share the same name.
+    pub fn build(self) -> Result<Node, RmakerNodeError> {
+        if self.node_id.is_empty() {
+            return Err(RmakerNodeError::EmptyNodeId);
+        }
+
+        match &self.info {
+            None => return Err(RmakerNodeError::InfoNotSet),
+            Some(info) if info.fw_version.is_empty() => {
+                return Err(RmakerNodeError::EmptyFwVersion)
+            }
+            _ => {}
+        }
+
+        if self.devices.is_empty() {
+            return Err(RmakerNodeError::NoDevices);
+        }
+
+        let mut node = Node {
+            node_id: self.node_id,
+            info: self.info,
+            attributes: self.attributes,
+            devices: Vec::with_capacity(self.devices.len()),
+            param_observer: None,
+            config_changed: AtomicBool::new(false),
+        };
+        for device in self.devices {
+            node.add_device(device)?;
+        }
+
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::device::{DeviceHandle, DeviceType};
+    use crate::param::Param;
+
+    fn switch(name: &str) -> Device {
+        let mut device = Device::new(name, DeviceType::Switch);
+        device.add_param(Param::new_power("Power", false)).unwrap();
+        device
+    }
+
+    // node with a switch whose callback records received params
+    fn recording_node() -> (Node, Arc<Mutex<Vec<HashMap<String, Value>>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_2 = received.clone();
+        let mut device = switch("Switch");
+        device.register_callback(Box::new(move |params, _handle: DeviceHandle| {
+            received_2.lock().unwrap().push(params);
+        }));
+
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(device).unwrap();
+        (node, received)
+    }
+
+    #[test]
+    fn build_valid_node() {
+        let node = NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "v1.0")
+            .attribute("serial".to_string(), "1234".to_string())
+            .device(switch("Switch"))
+            .build()
+            .unwrap();
+
+        assert_eq!(node.devices().len(), 1);
+        assert_eq!(node.devices()[0].name(), "Switch");
+    }
+
+    #[test]
+    fn build_rejects_empty_node_id() {
+        let result = NodeBuilder::new(String::new())
+            .info("Example Node", "v1.0")
+            .device(switch("Switch"))
+            .build();
+
+        assert!(matches!(result, Err(RmakerNodeError::EmptyNodeId)));
+    }
+
+    #[test]
+    fn build_rejects_missing_info() {
+        let result = NodeBuilder::new("node-1".to_string())
+            .device(switch("Switch"))
+            .build();
+
+        assert!(matches!(result, Err(RmakerNodeError::InfoNotSet)));
+    }
+
+    #[test]
+    fn build_rejects_empty_fw_version() {
+        let result = NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "")
+            .device(switch("Switch"))
+            .build();
+
+        assert!(matches!(result, Err(RmakerNodeError::EmptyFwVersion)));
+    }
+
+    #[test]
+    fn extended_info_is_serialized() {
+        let mut node = Node::new("node-1".to_string());
+        node.set_info(Info {
+            name: "Example Node".to_string(),
+            fw_version: "v1.0".to_string(),
+            model: Some("EX-100".to_string()),
+            project_name: Some("example".to_string()),
+            platform: Some("esp32c3".to_string()),
+        })
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&node).unwrap()["info"],
+            json!({
+                "name": "Example Node",
+                "fw_version": "v1.0",
+                "model": "EX-100",
+                "project_name": "example",
+                "platform": "esp32c3"
+            })
+        );
+    }
+
+    #[test]
+    fn unset_info_fields_are_omitted() {
+        let mut node = Node::new("node-1".to_string());
+        node.set_info(Info {
+            name: "Example Node".to_string(),
+            fw_version: "v1.0".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&node).unwrap()["info"],
+            json!({"name": "Example Node", "fw_version": "v1.0"})
+        );
+    }
+
+    #[test]
+    fn set_info_rejects_empty_fw_version() {
+        let mut node = Node::new("node-1".to_string());
+
+        let result = node.set_info(Info {
+            name: "Example Node".to_string(),
+            ..Default::default()
+        });
+
+        assert!(matches!(result, Err(RmakerNodeError::EmptyFwVersion)));
+        assert!(serde_json::to_value(&node).unwrap().get("info").is_none());
+    }
+
+    #[test]
+    fn build_rejects_node_without_devices() {
+        let result = NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "v1.0")
+            .build();
+
+        assert!(matches!(result, Err(RmakerNodeError::NoDevices)));
+    }
+
+    #[test]
+    fn add_device_rejects_duplicate_name() {
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(switch("Switch")).unwrap();
+
+        let result = node.add_device(switch("Switch"));
+
+        assert!(matches!(result, Err(RmakerNodeError::DuplicateDevice(name)) if name == "Switch"));
+        assert_eq!(node.devices().len(), 1);
+    }
+
+    #[test]
+    fn build_rejects_duplicate_devices() {
+        let result = NodeBuilder::new("node-1".to_string())
+            .info("Example Node", "v1.0")
+            .device(switch("Switch"))
+            .device(switch("Switch"))
+            .build();
+
+        assert!(matches!(result, Err(RmakerNodeError::DuplicateDevice(_))));
+    }
+
+    #[test]
+    fn dispatch_reports_unknown_device() {
+        let (node, received) = recording_node();
+
+        let unknown = node
+            .dispatch_params(json!({"Fan": {"Power": true}}))
+            .unwrap();
+
+        assert_eq!(unknown, vec!["Fan".to_string()]);
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dispatch_ignores_unknown_param() {
+        let (node, received) = recording_node();
+
+        let unknown = node
+            .dispatch_params(json!({"Switch": {"Speed": 3}}))
+            .unwrap();
+
+        assert!(unknown.is_empty());
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dispatch_ignores_mismatched_value_type() {
+        let (node, received) = recording_node();
+
+        node.dispatch_params(json!({"Switch": {"Power": "on"}}))
+            .unwrap();
+
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(
+            node.devices()[0].param("Power").unwrap().value(),
+            ParamValue::Bool(false)
+        );
+    }
+
+    #[test]
+    fn dispatch_forwards_only_valid_params() {
+        let (node, received) = recording_node();
+
+        node.dispatch_params(json!({"Switch": {"Power": true, "Speed": 3}}))
+            .unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![HashMap::from([("Power".to_string(), json!(true))])]
+        );
+    }
+
+    #[test]
+    fn dispatch_rejects_malformed_params() {
+        let (node, _) = recording_node();
+
+        assert!(node.dispatch_params(json!({"Switch": true})).is_err());
+    }
+
+    #[test]
+    fn similar_device_matches_case_insensitively() {
+        let (node, _) = recording_node();
+
+        assert_eq!(node.similar_device("switch"), Some("Switch"));
+        assert_eq!(node.similar_device("SWITCH"), Some("Switch"));
+    }
+
+    #[test]
+    fn similar_device_ignores_other_names() {
+        let (node, _) = recording_node();
+
+        assert_eq!(node.similar_device("Swtich"), None);
+        assert_eq!(node.similar_device("Fan"), None);
+    }
+
+    #[test]
+    fn command_for_unknown_device_is_ignored() {
+        let (node, received) = recording_node();
+
+        assert!(!node.exeute_device_callback(
+            "switch",
+            HashMap::from([("Power".to_string(), json!(true))])
+        ));
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "mock-mqtt")]
+    #[test]
+    fn rename_marks_node_config_changed() {
+        let _mqtt = crate::rmaker_mqtt::mock::lock();
+        let mut device = switch("Renamed Switch");
+        device.add_name_param().unwrap();
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(device).unwrap();
+
+        node.dispatch_params(json!({"Renamed Switch": {"Power": true}}))
+            .unwrap();
+        assert!(!node.take_config_changed());
+
+        node.dispatch_params(json!({"Renamed Switch": {"Name": "Porch"}}))
+            .unwrap();
+        assert!(node.take_config_changed());
+        assert!(!node.take_config_changed());
+    }
+
+    // NaN has no JSON representation, hence it is converted to null
+    #[test]
+    fn unserializable_param_value_is_rejected() {
+        let mut device = Device::new("Sensor", DeviceType::TemperatureSensor);
+        device
+            .add_param(Param::new_temperature("Temperature", f64::NAN))
+            .unwrap();
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(switch("Switch")).unwrap();
+        node.add_device(device).unwrap();
+
+        assert!(matches!(
+            node.get_param_values(),
+            Err(RmakerNodeError::InvalidParamValue(param)) if param == "Sensor.Temperature"
+        ));
+    }
+
+    fn serialized_device_names(node: &Node) -> Vec<String> {
+        serde_json::to_value(node).unwrap()["devices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|device| device["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn device_order_changes_serialized_order() {
+        let mut node = Node::new("node-1".to_string());
+        for name in ["Fan", "Light", "Plug"] {
+            node.add_device(switch(name)).unwrap();
+        }
+        assert_eq!(serialized_device_names(&node), ["Fan", "Light", "Plug"]);
+
+        node.set_device_order(&["Plug", "Light"]).unwrap();
+
+        // devices which are not named keep their order after the named ones
+        assert_eq!(serialized_device_names(&node), ["Plug", "Light", "Fan"]);
+    }
+
+    #[test]
+    fn device_order_with_unknown_device_is_rejected() {
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(switch("Fan")).unwrap();
+        node.add_device(switch("Light")).unwrap();
+
+        assert!(matches!(
+            node.set_device_order(&["Light", "Heater"]),
+            Err(RmakerNodeError::DeviceNotFound(name)) if name == "Heater"
+        ));
+        assert_eq!(serialized_device_names(&node), ["Fan", "Light"]);
+    }
+
+    #[test]
+    fn param_observer_is_notified_of_changes_across_devices() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_2 = changes.clone();
+        let mut node = Node::new("node-1".to_string());
+        node.add_device(switch("Fan")).unwrap();
+        node.add_device(switch("Light")).unwrap();
+        node.set_param_observer(Box::new(move |device, param, value| {
+            changes_2
+                .lock()
+                .unwrap()
+                .push((device.to_string(), param.to_string(), value.clone()));
+        }));
+
+        node.dispatch_params(json!({"Fan": {"Power": true}, "Light": {"Power": false}}))
+            .unwrap();
+
+        let mut changes = changes.lock().unwrap().clone();
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            changes,
+            [
+                (
+                    "Fan".to_string(),
+                    "Power".to_string(),
+                    ParamValue::Bool(true)
+                ),
+                (
+                    "Light".to_string(),
+                    "Power".to_string(),
+                    ParamValue::Bool(false)
+                ),
+            ]
+        );
     }
 }
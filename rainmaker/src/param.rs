@@ -2,35 +2,84 @@
 //!
 //! A single instance of parameter can be assigned to multiple devices.
 //!
-//! Initialization for standard parameters(Power, Brightness, Hue, Saturation) can be done using specified standard methods.
+//! Initialization for standard parameters(Power, Brightness, Hue, Saturation, CCT) can be done using specified standard methods.
+//!
+//! [RgbLight] bundles the standard parameters of a color light and converts their values to RGB.
 
+pub use rgb::RGB8;
 use serde::Serialize;
 use serde_json::{Number, Value};
-use std::collections::HashSet;
-use std::sync::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use crate::{
+    device::{Device, DeviceHandle, DeviceType},
+    error::{RmakerDeviceError, RmakerError},
+    utils::{wrap_in_arc_mutex, WrappedInArcMutex},
+};
+
+static NUMBER_HANDLING: OnceLock<NumberHandling> = OnceLock::new();
+
+/// Handling of received numbers which do not fit the integer parameter they are sent for, e.g. `50.7` or
+/// integers beyond the range of `i64`.
+///
+/// Can be set using [`Rainmaker::set_number_handling`](crate::Rainmaker::set_number_handling).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberHandling {
+    /// Only integral values within the range of `i64` are accepted, e.g. `50.0`. Other values are ignored.
+    #[default]
+    Strict,
+    /// Floats are rounded to the nearest integer. Values are clamped to the bounds of parameter, if set,
+    /// else to the range of `i64`.
+    Lenient,
+}
+
+// fails if any value is received already
+pub(crate) fn set_number_handling(handling: NumberHandling) -> Result<(), RmakerError> {
+    NUMBER_HANDLING
+        .set(handling)
+        .map_err(|_| RmakerError::AlreadyInitialized)
+}
+
+fn number_handling() -> NumberHandling {
+    *NUMBER_HANDLING.get_or_init(NumberHandling::default)
+}
 
 #[derive(Debug, Serialize)]
 pub struct Param {
     name: String,
+    #[serde(rename = "type")]
     param_type: ParamTypes,
     ui_type: ParamUi,
+    #[serde(serialize_with = "serialize_properties")]
     properties: HashSet<ParamProperty>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    bounds: Option<ParamBounds>,
-    #[serde(rename = "data_type")]
-    value: Mutex<ParamValue>,
+    bounds: Option<Bounds>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    precision: Option<u8>,
+    #[serde(rename = "data_type", serialize_with = "serialize_data_type")]
+    value: WrappedInArcMutex<ParamValue>,
+    #[serde(skip)]
+    momentary: bool,
+    #[serde(skip)]
+    weak_update: bool,
 }
 
 /// Set of access mode parameter.
-#[derive(Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ParamProperty {
     Read,
     Write,
+    /// Reported values are stored as time series by RainMaker cloud.
+    #[serde(rename = "time_series")]
+    TimeSeries,
 }
 
 /// Set of the type of parameter value.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParamValue {
     String(String),
     Bool(bool),
@@ -43,7 +92,7 @@ pub enum ParamValue {
 /// ESP RainMaker provides a set of standard parameters. These are provided with a UI and have special handling in clients like Alexa/Google Home.
 ///
 /// Refer [device list](https://rainmaker.espressif.com/docs/standard-types).
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum ParamTypes {
     #[serde(rename = "esp.param.name")]
     Name,
@@ -129,10 +178,12 @@ pub enum ParamTypes {
     CODetectionState,
     #[serde(rename = "esp.param.burglary-alarm")]
     BurglaryAlarmState,
+    #[serde(rename = "esp.param.rssi")]
+    Rssi,
 }
 
 /// Set of standard UI elements.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum ParamUi {
     #[serde(rename = "esp.ui.text")]
     Text,
@@ -156,11 +207,28 @@ pub enum ParamUi {
     Hidden,
 }
 
+/// Range of values accepted by a numeric parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ParamBounds {
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+}
+
+/// Range of values accepted by a float parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FloatParamBounds {
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+}
+
+// a parameter has either integer or float bounds, both serialized as "bounds"
 #[derive(Debug, Serialize)]
-struct ParamBounds {
-    min: i32,
-    max: i32,
-    step: i32,
+#[serde(untagged)]
+enum Bounds {
+    Integer(ParamBounds),
+    Float(FloatParamBounds),
 }
 
 impl Param {
@@ -174,11 +242,14 @@ impl Param {
     ) -> Param {
         Param {
             name: name.to_owned(),
-            value: Mutex::new(initial_state),
+            value: wrap_in_arc_mutex(initial_state),
             param_type,
             properties,
             ui_type,
             bounds: None,
+            precision: None,
+            momentary: false,
+            weak_update: false,
         }
     }
 
@@ -187,6 +258,77 @@ impl Param {
         &self.name
     }
 
+    /// Returns the standard type of parameter.
+    pub fn param_type(&self) -> &ParamTypes {
+        &self.param_type
+    }
+
+    /// Returns the UI element used for parameter in phone apps.
+    pub fn ui_type(&self) -> &ParamUi {
+        &self.ui_type
+    }
+
+    /// Returns the bounds of parameter, if set using [`add_bounds`](Param::add_bounds).
+    pub fn bounds(&self) -> Option<&ParamBounds> {
+        match &self.bounds {
+            Some(Bounds::Integer(bounds)) => Some(bounds),
+            _ => None,
+        }
+    }
+
+    /// Returns the bounds of parameter, if set using [`add_float_bounds`](Param::add_float_bounds).
+    pub fn float_bounds(&self) -> Option<&FloatParamBounds> {
+        match &self.bounds {
+            Some(Bounds::Float(bounds)) => Some(bounds),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of decimal places shown for value in phone apps, if set.
+    pub fn precision(&self) -> Option<u8> {
+        self.precision
+    }
+
+    /// Returns access modes of parameter.
+    pub fn properties(&self) -> &HashSet<ParamProperty> {
+        &self.properties
+    }
+
+    /// Returns true if parameter can be modified from RainMaker cloud or local control.
+    pub fn is_writable(&self) -> bool {
+        self.properties.contains(&ParamProperty::Write)
+    }
+
+    /// Returns true for momentary parameters which reset after every write, e.g. [Param::new_trigger].
+    ///
+    /// Momentary parameters are reported as `false` by the agent once the write is handled, values reported by the
+    /// application are ignored.
+    pub fn is_momentary(&self) -> bool {
+        self.momentary
+    }
+
+    /// Returns true if received values are applied only once reported by the device.
+    ///
+    /// See [`set_weak_update`](Param::set_weak_update).
+    pub fn is_weak_update(&self) -> bool {
+        self.weak_update
+    }
+
+    /// Treats received values as requests, which are applied only once the device reports the value it actually
+    /// set, e.g. a clamped brightness.
+    ///
+    /// By default, received values are applied before the device callback is invoked. With weak updates, the state
+    /// of parameter seen by callback and observer stays unchanged till the callback reports a value.
+    /// ```rust
+    /// let mut brightness = Param::new_brightness("Brightness", 50);
+    /// brightness.set_weak_update(true);
+    /// // in device callback
+    /// device_handle.update_and_report(HashMap::from([("Brightness".to_string(), json!(100))]))?;
+    /// ```
+    pub fn set_weak_update(&mut self, weak: bool) {
+        self.weak_update = weak;
+    }
+
     /// Returns current state of parameter.
     pub fn value(&self) -> ParamValue {
         self.value.lock().unwrap().clone()
@@ -196,9 +338,120 @@ impl Param {
         *self.value.lock().unwrap() = value;
     }
 
+    // shared handle to the current state, used for updating value from outside of device
+    pub(crate) fn value_handle(&self) -> WrappedInArcMutex<ParamValue> {
+        self.value.clone()
+    }
+
+    /// Updates the current state of parameter from a received JSON value.
+    ///
+    /// The value is converted to the data type of the parameter. Returns false if conversion is not possible.
+    pub(crate) fn set_value_from_json(&self, value: &Value) -> bool {
+        match self.value_from_json(value) {
+            Some(new_val) => {
+                self.set_value(new_val);
+                true
+            }
+            None => {
+                log::error!("invalid value {} received for param {}", value, self.name);
+                false
+            }
+        }
+    }
+
+    // converts received JSON value to the data type of parameter. returns None if types don't match
+    pub(crate) fn value_from_json(&self, value: &Value) -> Option<ParamValue> {
+        self.value_from_json_with(value, number_handling())
+    }
+
+    fn value_from_json_with(&self, value: &Value, handling: NumberHandling) -> Option<ParamValue> {
+        let received = ParamValue::try_from(value).ok()?;
+        match (&*self.value.lock().unwrap(), received) {
+            (ParamValue::Integer(_), ParamValue::Integer(v)) => {
+                Some(ParamValue::Integer(self.clamp_to_bounds(v, handling)))
+            }
+            (ParamValue::String(_), v @ ParamValue::String(_))
+            | (ParamValue::Bool(_), v @ ParamValue::Bool(_))
+            | (ParamValue::Float(_), v @ ParamValue::Float(_)) => Some(v),
+            // clients may send integral values for float params
+            (ParamValue::Float(_), ParamValue::Integer(v)) => Some(ParamValue::Float(v as f64)),
+            // floats and integers beyond i64 range, which are received as floats, for integer params
+            (ParamValue::Integer(_), ParamValue::Float(v)) => self.integer_from_float(v, handling),
+            // some clients, e.g. voice assistants, send 0/1 for bool params
+            (ParamValue::Bool(_), ParamValue::Integer(v @ (0 | 1))) => {
+                Some(ParamValue::Bool(v == 1))
+            }
+            _ => None,
+        }
+    }
+
+    fn integer_from_float(&self, value: f64, handling: NumberHandling) -> Option<ParamValue> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        match handling {
+            // i64::MAX as f64 rounds up to 2^63, which is out of range
+            NumberHandling::Strict
+                if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 =>
+            {
+                Some(ParamValue::Integer(value as i64))
+            }
+            NumberHandling::Strict => None,
+            NumberHandling::Lenient => {
+                // casting saturates at the range of i64
+                Some(ParamValue::Integer(
+                    self.clamp_to_bounds(value.round() as i64, handling),
+                ))
+            }
+        }
+    }
+
+    // values are clamped only in lenient mode
+    fn clamp_to_bounds(&self, value: i64, handling: NumberHandling) -> i64 {
+        match (handling, self.bounds()) {
+            // not using clamp() since it panics for misconfigured bounds with min > max
+            (NumberHandling::Lenient, Some(bounds)) => {
+                value.max(bounds.min as i64).min(bounds.max as i64)
+            }
+            _ => value,
+        }
+    }
+
     /// Assigns minimum and maximum value to a parameter.
+    ///
+    /// Replaces float bounds, if set.
     pub fn add_bounds(&mut self, min: i32, max: i32, step: i32) {
-        self.bounds = Some(ParamBounds { min, max, step })
+        self.bounds = Some(Bounds::Integer(ParamBounds { min, max, step }))
+    }
+
+    /// Assigns minimum and maximum value to a float parameter.
+    ///
+    /// Replaces integer bounds, if set.
+    pub fn add_float_bounds(&mut self, min: f64, max: f64, step: f64) {
+        self.bounds = Some(Bounds::Float(FloatParamBounds { min, max, step }))
+    }
+
+    /// Sets the number of decimal places shown for value in phone apps.
+    pub fn set_precision(&mut self, precision: u8) {
+        self.precision = Some(precision);
+    }
+
+    /// Standard function to add Name parameter, which holds the display name of device.
+    ///
+    /// Users can rename the device from phone apps by writing to this parameter.
+    pub fn new_name(name: &str, initial_value: &str) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        Self::new(
+            name,
+            ParamValue::String(initial_value.to_string()),
+            ParamTypes::Name,
+            param_properties,
+            ParamUi::Text,
+        )
     }
 
     /// Standard function to add Power parameter.
@@ -216,6 +469,115 @@ impl Param {
         )
     }
 
+    /// Standard function to add read-only Temperature parameter. Reported values are stored as time series.
+    pub fn new_temperature(name: &str, initial_value: f64) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::TimeSeries);
+
+        Self::new(
+            name,
+            ParamValue::Float(initial_value),
+            ParamTypes::AmbientTemperature,
+            param_properties,
+            ParamUi::Text,
+        )
+    }
+
+    /// Standard function to add read-only WiFi signal strength(RSSI in dBm) parameter. Reported values are stored as time series.
+    pub fn new_rssi(name: &str, initial_value: i32) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::TimeSeries);
+
+        Self::new(
+            name,
+            ParamValue::Integer(initial_value as i64),
+            ParamTypes::Rssi,
+            param_properties,
+            ParamUi::Text,
+        )
+    }
+
+    /// Generic function to add a toggle parameter.
+    pub fn new_toggle(name: &str, initial_value: bool) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        Self::new(
+            name,
+            ParamValue::Bool(initial_value),
+            ParamTypes::ToggleController,
+            param_properties,
+            ParamUi::ToggleSwitch,
+        )
+    }
+
+    /// Generic function to add a slider parameter with provided bounds.
+    /// ```rust
+    /// let volume = Param::new_slider("Volume", 0, 11, 1, 5);
+    /// ```
+    pub fn new_slider(name: &str, min: i32, max: i32, step: i32, initial_value: i32) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Integer(initial_value as i64),
+            ParamTypes::RangeController,
+            param_properties,
+            ParamUi::Slider,
+        );
+        param.add_bounds(min, max, step);
+
+        param
+    }
+
+    /// Generic function to add a float slider parameter with provided bounds.
+    ///
+    /// Precision is derived from the step, e.g. one decimal place for step of 0.5.
+    /// ```rust
+    /// let setpoint = Param::new_float_slider("Setpoint", 16.0, 30.0, 0.5, 22.0);
+    /// ```
+    pub fn new_float_slider(name: &str, min: f64, max: f64, step: f64, initial_value: f64) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Float(initial_value),
+            ParamTypes::RangeController,
+            param_properties,
+            ParamUi::Slider,
+        );
+        param.add_float_bounds(min, max, step);
+        param.set_precision(step_precision(step));
+
+        param
+    }
+
+    /// Standard function to add a momentary push button parameter, e.g. for testing a doorbell chime.
+    ///
+    /// The parameter is write-only. Device callback receives `true` on every press, after which the value resets to `false`.
+    pub fn new_trigger(name: &str) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Bool(false),
+            ParamTypes::ToggleController,
+            param_properties,
+            ParamUi::PushButton,
+        );
+        param.momentary = true;
+
+        param
+    }
+
     /// Standard function to add Brightness parameter.
     pub fn new_brightness(name: &str, initial_value: u32) -> Self {
         let mut param_properties = HashSet::new();
@@ -234,6 +596,47 @@ impl Param {
         param
     }
 
+    /// Standard function to add Direction parameter of a fan, `0` for forward(clockwise) and `1` for reverse.
+    ///
+    /// Shown as a dropdown in phone apps. Initial direction is forward.
+    pub fn new_direction(name: &str) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Integer(0),
+            ParamTypes::Direction,
+            param_properties,
+            ParamUi::Dropdown,
+        );
+        param.add_bounds(0, 1, 1);
+
+        param
+    }
+
+    /// Standard function to add Speed parameter of a fan with the provided number of levels, `0` being off.
+    /// ```rust
+    /// let speed = Param::new_speed("Speed", 5);
+    /// ```
+    pub fn new_speed(name: &str, levels: u32) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Integer(0),
+            ParamTypes::Speed,
+            param_properties,
+            ParamUi::Slider,
+        );
+        param.add_bounds(0, levels.min(i32::MAX as u32) as i32, 1);
+
+        param
+    }
+
     /// Standard function to add Hue parameter.
     pub fn new_hue(name: &str, initial_value: u32) -> Self {
         let mut param_properties = HashSet::new();
@@ -270,6 +673,208 @@ impl Param {
 
         param
     }
+
+    /// Standard function to add CCT(color temperature) parameter. Bounds and value are in Kelvin.
+    pub fn new_cct(name: &str, min_k: u32, max_k: u32, initial_value: u32) -> Self {
+        let mut param_properties = HashSet::new();
+        param_properties.insert(ParamProperty::Read);
+        param_properties.insert(ParamProperty::Write);
+
+        let mut param = Self::new(
+            name,
+            ParamValue::Integer(i64::from(initial_value)),
+            ParamTypes::CCT,
+            param_properties,
+            ParamUi::Slider,
+        );
+        // bounds beyond i32 are saturated instead of wrapping around
+        let min = i32::try_from(min_k).unwrap_or(i32::MAX);
+        let max = i32::try_from(max_k).unwrap_or(i32::MAX);
+        param.add_bounds(min, max, 100);
+        param.set_value(ParamValue::Integer(
+            param.clamp_to_bounds(i64::from(initial_value), NumberHandling::Lenient),
+        ));
+
+        param
+    }
+}
+
+/// State of a color light made of standard Power, Hue, Saturation and Brightness parameters.
+///
+/// Example for creating a light device which receives the computed color:
+/// ```rust
+/// let light = RgbLight::new(true, 0, 100, 15);
+/// let device = RgbLight::device("Light", light, |on, rgb| {
+///     log::info!("light on: {}, color: {:?}", on, rgb);
+/// })?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RgbLight {
+    pub power: bool,
+    /// Hue in degrees, 0-360.
+    pub hue: u16,
+    /// Saturation in percent, 0-100.
+    pub saturation: u8,
+    /// Brightness in percent, 0-100.
+    pub brightness: u8,
+}
+
+impl RgbLight {
+    pub const POWER: &'static str = "Power";
+    pub const HUE: &'static str = "Hue";
+    pub const SATURATION: &'static str = "Saturation";
+    pub const BRIGHTNESS: &'static str = "Brightness";
+
+    pub fn new(power: bool, hue: u16, saturation: u8, brightness: u8) -> Self {
+        Self {
+            power,
+            hue,
+            saturation,
+            brightness,
+        }
+    }
+
+    /// Returns standard parameters initialized with the current state.
+    pub fn params(&self) -> Vec<Param> {
+        vec![
+            Param::new_power(Self::POWER, self.power),
+            Param::new_brightness(Self::BRIGHTNESS, self.brightness as u32),
+            Param::new_satuation(Self::SATURATION, self.saturation as u32),
+            Param::new_hue(Self::HUE, self.hue as u32),
+        ]
+    }
+
+    /// Reads the state from parameters of a device. Missing parameters keep the current value.
+    pub fn update_from_params(&mut self, params: &[Param]) {
+        for param in params {
+            match (param.name(), param.value()) {
+                (Self::POWER, ParamValue::Bool(power)) => self.power = power,
+                (Self::HUE, ParamValue::Integer(hue)) => self.hue = hue.clamp(0, 360) as u16,
+                (Self::SATURATION, ParamValue::Integer(sat)) => {
+                    self.saturation = sat.clamp(0, 100) as u8
+                }
+                (Self::BRIGHTNESS, ParamValue::Integer(brightness)) => {
+                    self.brightness = brightness.clamp(0, 100) as u8
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Merges received values into the state. Values which are not received are left unchanged.
+    pub fn update(&mut self, values: &HashMap<String, Value>) {
+        for (name, value) in values {
+            match (name.as_str(), value) {
+                (Self::POWER, Value::Bool(power)) => self.power = *power,
+                (Self::HUE, value) => {
+                    if let Some(hue) = value.as_u64() {
+                        self.hue = hue.min(360) as u16
+                    }
+                }
+                (Self::SATURATION, value) => {
+                    if let Some(sat) = value.as_u64() {
+                        self.saturation = sat.min(100) as u8
+                    }
+                }
+                (Self::BRIGHTNESS, value) => {
+                    if let Some(brightness) = value.as_u64() {
+                        self.brightness = brightness.min(100) as u8
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the color of light. Black is returned if the light is off.
+    pub fn rgb(&self) -> RGB8 {
+        if !self.power {
+            return RGB8::default();
+        }
+
+        hsv_to_rgb(self.hue, self.saturation, self.brightness)
+    }
+
+    /// Same as [`rgb`](RgbLight::rgb), but gamma corrected using [gamma_correct].
+    pub fn rgb_with_gamma(&self, gamma: f64) -> RGB8 {
+        gamma_correct(self.rgb(), gamma)
+    }
+
+    /// Creates a light device with the standard parameters and `Power` as primary parameter.
+    ///
+    /// The callback is invoked with the power state and computed color whenever values are received,
+    /// after which the received values are reported.
+    pub fn device(
+        name: &str,
+        initial: RgbLight,
+        cb: impl Fn(bool, RGB8) + Send + Sync + 'static,
+    ) -> Result<Device, RmakerDeviceError> {
+        let mut device = Device::new(name, DeviceType::Lightbulb);
+        for param in initial.params() {
+            device.add_param(param)?;
+        }
+        device.set_primary_param(Self::POWER)?;
+
+        device.register_callback(Box::new(
+            move |params: HashMap<String, Value>, device_handle: DeviceHandle| {
+                // params of device handle already reflect the received values
+                let mut light = initial;
+                light.update_from_params(device_handle.params);
+                cb(light.power, light.rgb());
+                if device_handle.update_and_report(params).is_err() {
+                    log::error!("could not report state of light");
+                }
+            },
+        ));
+
+        Ok(device)
+    }
+}
+
+/// Converts color from HSV(hue: 0-360, saturation: 0-100, value: 0-100) to RGB.
+pub fn hsv_to_rgb(h: u16, s: u8, v: u8) -> RGB8 {
+    let s = s.min(100) as f64 / 100.0; // Convert to range 0.0 to 1.0
+    let v = v.min(100) as f64 / 100.0; // Convert to range 0.0 to 1.0
+    let c = v * s; // Chroma
+    let h_prime = (h % 360) as f64 / 60.0; // Sector index
+    let x = c * (1.0 - ((h_prime % 2.0) - 1.0).abs());
+    let m = v - c;
+
+    let (r1, g1, b1) = if h_prime < 1.0 {
+        (c, x, 0.0)
+    } else if h_prime < 2.0 {
+        (x, c, 0.0)
+    } else if h_prime < 3.0 {
+        (0.0, c, x)
+    } else if h_prime < 4.0 {
+        (0.0, x, c)
+    } else if h_prime < 5.0 {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
+
+    // Convert back to RGB range [0, 255]
+    let r = ((r1 + m) * 255.0).round() as u8;
+    let g = ((g1 + m) * 255.0).round() as u8;
+    let b = ((b1 + m) * 255.0).round() as u8;
+
+    RGB8::new(r, g, b)
+}
+
+/// Typical gamma of LEDs, for use with [gamma_correct].
+pub const LED_GAMMA: f64 = 2.2;
+
+/// Applies gamma correction to color, so that brightness changes look perceptually linear on LEDs.
+///
+/// Each channel is mapped as `255 * (c / 255) ^ gamma`. Gamma of 1.0 leaves the color unchanged.
+/// ```rust
+/// let rgb = gamma_correct(hsv_to_rgb(0, 100, 50), LED_GAMMA); // RGB8 { r: 56, g: 0, b: 0 }
+/// ```
+pub fn gamma_correct(color: RGB8, gamma: f64) -> RGB8 {
+    let correct = |c: u8| ((c as f64 / 255.0).powf(gamma) * 255.0).round() as u8;
+
+    RGB8::new(correct(color.r), correct(color.g), correct(color.b))
 }
 
 impl Serialize for ParamValue {
@@ -286,13 +891,539 @@ impl Serialize for ParamValue {
     }
 }
 
+fn serialize_data_type<S>(
+    value: &WrappedInArcMutex<ParamValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    value.lock().unwrap().serialize(serializer)
+}
+
+// decimal places required for showing multiples of step, up to 6
+fn step_precision(step: f64) -> u8 {
+    let mut precision = 0;
+    let mut scaled = step.abs();
+    while precision < 6 && (scaled - scaled.round()).abs() > 1e-9 {
+        scaled *= 10.0;
+        precision += 1;
+    }
+
+    precision
+}
+
+// properties are serialized in a fixed order so that node configuration is identical across boots
+fn serialize_properties<S>(
+    properties: &HashSet<ParamProperty>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut properties: Vec<_> = properties.iter().collect();
+    properties.sort();
+    properties.serialize(serializer)
+}
+
+/// Converts JSON booleans, numbers and strings to the respective variant.
+///
+/// Integers which do not fit in `i64` are converted to [`ParamValue::Float`].
+/// Returns an error for null, arrays and objects.
+/// ```rust
+/// let value = ParamValue::try_from(&json!(true))?;
+/// assert_eq!(value, ParamValue::Bool(true));
+/// ```
+impl TryFrom<&Value> for ParamValue {
+    type Error = RmakerDeviceError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(v) => Ok(Self::Bool(*v)),
+            Value::String(v) => Ok(Self::String(v.clone())),
+            Value::Number(v) => match (v.as_i64(), v.as_f64()) {
+                (Some(v), _) => Ok(Self::Integer(v)),
+                (None, Some(v)) => Ok(Self::Float(v)),
+                (None, None) => Err(RmakerDeviceError::UnsupportedValue(value.to_string())),
+            },
+            _ => Err(RmakerDeviceError::UnsupportedValue(value.to_string())),
+        }
+    }
+}
+
 impl From<ParamValue> for Value {
     fn from(value: ParamValue) -> Self {
         match value {
             ParamValue::String(v) => Self::String(v),
             ParamValue::Bool(v) => Self::Bool(v),
             ParamValue::Integer(v) => Self::Number(Number::from(v)),
-            ParamValue::Float(v) => Self::Number(Number::from_f64(v).unwrap()),
+            // NaN and infinite values are not representable in JSON
+            ParamValue::Float(v) => Number::from_f64(v).map_or(Self::Null, Self::Number),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn gamma_correct_matches_reference_values() {
+        // reference values of 255 * (c / 255) ^ 2.2
+        let cases = [(0, 0), (1, 0), (64, 12), (128, 56), (191, 135), (255, 255)];
+
+        for (c, expected) in cases {
+            assert_eq!(
+                gamma_correct(RGB8::new(c, c, c), LED_GAMMA),
+                RGB8::new(expected, expected, expected),
+                "{c}"
+            );
+        }
+    }
+
+    #[test]
+    fn gamma_correct_channels_independently() {
+        assert_eq!(
+            gamma_correct(RGB8::new(255, 128, 0), LED_GAMMA),
+            RGB8::new(255, 56, 0)
+        );
+    }
+
+    #[test]
+    fn gamma_of_one_leaves_color_unchanged() {
+        let color = RGB8::new(12, 128, 250);
+
+        assert_eq!(gamma_correct(color, 1.0), color);
+    }
+
+    #[test]
+    fn slider_config_has_provided_bounds() {
+        let volume = Param::new_slider("Volume", 0, 11, 1, 5);
+
+        assert_eq!(
+            serde_json::to_value(&volume).unwrap(),
+            json!({
+                "name": "Volume",
+                "type": "esp.param.range",
+                "ui_type": "esp.ui.slider",
+                "properties": ["read", "write"],
+                "bounds": {"min": 0, "max": 11, "step": 1},
+                "data_type": "int"
+            })
+        );
+        assert_eq!(volume.value(), ParamValue::Integer(5));
+    }
+
+    #[test]
+    fn accessors_return_metadata_set_at_construction() {
+        let volume = Param::new_slider("Volume", 0, 11, 1, 5);
+
+        assert_eq!(volume.param_type(), &ParamTypes::RangeController);
+        assert_eq!(volume.ui_type(), &ParamUi::Slider);
+        assert_eq!(
+            volume.bounds(),
+            Some(&ParamBounds {
+                min: 0,
+                max: 11,
+                step: 1
+            })
+        );
+        assert_eq!(volume.float_bounds(), None);
+    }
+
+    #[test]
+    fn param_without_bounds_has_none() {
+        let power = Param::new_power("Power", false);
+
+        assert_eq!(power.param_type(), &ParamTypes::Power);
+        assert_eq!(power.ui_type(), &ParamUi::ToggleSwitch);
+        assert_eq!(power.bounds(), None);
+    }
+
+    #[test]
+    fn toggle_config_has_no_bounds() {
+        let toggle = Param::new_toggle("Eco Mode", true);
+
+        assert_eq!(
+            serde_json::to_value(&toggle).unwrap(),
+            json!({
+                "name": "Eco Mode",
+                "type": "esp.param.toggle",
+                "ui_type": "esp.ui.toggle",
+                "properties": ["read", "write"],
+                "data_type": "bool"
+            })
+        );
+        assert_eq!(toggle.value(), ParamValue::Bool(true));
+    }
+
+    #[test]
+    fn direction_config_is_binary_dropdown() {
+        assert_eq!(
+            serde_json::to_value(Param::new_direction("Direction")).unwrap(),
+            json!({
+                "name": "Direction",
+                "type": "esp.param.direction",
+                "ui_type": "esp.ui.dropdown",
+                "properties": ["read", "write"],
+                "bounds": {"min": 0, "max": 1, "step": 1},
+                "data_type": "int"
+            })
+        );
+    }
+
+    #[test]
+    fn speed_config_has_provided_levels() {
+        assert_eq!(
+            serde_json::to_value(Param::new_speed("Speed", 5)).unwrap(),
+            json!({
+                "name": "Speed",
+                "type": "esp.param.speed",
+                "ui_type": "esp.ui.slider",
+                "properties": ["read", "write"],
+                "bounds": {"min": 0, "max": 5, "step": 1},
+                "data_type": "int"
+            })
+        );
+    }
+
+    #[test]
+    fn cct_config_has_kelvin_bounds() {
+        let cct = Param::new_cct("CCT", 2700, 6500, 4000);
+
+        assert_eq!(
+            serde_json::to_value(&cct).unwrap(),
+            json!({
+                "name": "CCT",
+                "type": "esp.param.cct",
+                "ui_type": "esp.ui.slider",
+                "properties": ["read", "write"],
+                "bounds": {"min": 2700, "max": 6500, "step": 100},
+                "data_type": "int"
+            })
+        );
+        assert_eq!(cct.value(), ParamValue::Integer(4000));
+    }
+
+    #[test]
+    fn cct_initial_value_is_clamped_to_bounds() {
+        assert_eq!(
+            Param::new_cct("CCT", 2700, 6500, 2000).value(),
+            ParamValue::Integer(2700)
+        );
+        assert_eq!(
+            Param::new_cct("CCT", 2700, 6500, 9000).value(),
+            ParamValue::Integer(6500)
+        );
+    }
+
+    #[test]
+    fn cct_bounds_beyond_i32_are_saturated() {
+        let cct = Param::new_cct("CCT", 2700, u32::MAX, 4000);
+
+        assert_eq!(
+            cct.bounds(),
+            Some(&ParamBounds {
+                min: 2700,
+                max: i32::MAX,
+                step: 100
+            })
+        );
+    }
+
+    #[test]
+    fn hsv_to_rgb_primary_colors() {
+        assert_eq!(hsv_to_rgb(0, 100, 100), RGB8::new(255, 0, 0));
+        assert_eq!(hsv_to_rgb(120, 100, 100), RGB8::new(0, 255, 0));
+        assert_eq!(hsv_to_rgb(240, 100, 100), RGB8::new(0, 0, 255));
+        assert_eq!(hsv_to_rgb(360, 100, 100), RGB8::new(255, 0, 0));
+    }
+
+    #[test]
+    fn hsv_to_rgb_saturation_and_value() {
+        assert_eq!(hsv_to_rgb(200, 0, 100), RGB8::new(255, 255, 255));
+        assert_eq!(hsv_to_rgb(200, 100, 0), RGB8::new(0, 0, 0));
+        assert_eq!(hsv_to_rgb(240, 100, 50), RGB8::new(0, 0, 128));
+        assert_eq!(hsv_to_rgb(60, 100, 100), RGB8::new(255, 255, 0));
+    }
+
+    #[test]
+    fn rgb_light_partial_update_keeps_other_values() {
+        let mut light = RgbLight::new(true, 0, 100, 100);
+
+        light.update(&HashMap::from([("Hue".to_string(), json!(120))]));
+
+        assert_eq!(light, RgbLight::new(true, 120, 100, 100));
+        assert_eq!(light.rgb(), RGB8::new(0, 255, 0));
+    }
+
+    #[test]
+    fn rgb_light_update_clamps_values() {
+        let mut light = RgbLight::new(true, 0, 100, 100);
+
+        light.update(&HashMap::from([
+            ("Hue".to_string(), json!(400)),
+            ("Brightness".to_string(), json!(150)),
+        ]));
+
+        assert_eq!(light, RgbLight::new(true, 360, 100, 100));
+    }
+
+    #[test]
+    fn rgb_light_is_black_when_off() {
+        let mut light = RgbLight::new(true, 240, 100, 100);
+
+        light.update(&HashMap::from([("Power".to_string(), json!(false))]));
+
+        assert_eq!(light.rgb(), RGB8::default());
+    }
+
+    #[test]
+    fn rgb_light_update_from_params() {
+        let mut light = RgbLight::new(false, 0, 0, 0);
+
+        light.update_from_params(&RgbLight::new(true, 120, 50, 80).params());
+
+        assert_eq!(light, RgbLight::new(true, 120, 50, 80));
+    }
+
+    #[test]
+    fn param_value_from_json_kinds() {
+        assert_eq!(
+            ParamValue::try_from(&json!(true)).unwrap(),
+            ParamValue::Bool(true)
+        );
+        assert_eq!(
+            ParamValue::try_from(&json!("Light")).unwrap(),
+            ParamValue::String("Light".to_string())
+        );
+        assert_eq!(
+            ParamValue::try_from(&json!(42)).unwrap(),
+            ParamValue::Integer(42)
+        );
+        assert_eq!(
+            ParamValue::try_from(&json!(-7)).unwrap(),
+            ParamValue::Integer(-7)
+        );
+        assert_eq!(
+            ParamValue::try_from(&json!(22.5)).unwrap(),
+            ParamValue::Float(22.5)
+        );
+    }
+
+    #[test]
+    fn param_value_from_json_integer_beyond_i64() {
+        assert_eq!(
+            ParamValue::try_from(&json!(u64::MAX)).unwrap(),
+            ParamValue::Float(u64::MAX as f64)
+        );
+    }
+
+    #[test]
+    fn param_value_from_json_rejects_unsupported_kinds() {
+        for value in [json!(null), json!([1, 2]), json!({"on": true})] {
+            assert!(matches!(
+                ParamValue::try_from(&value),
+                Err(RmakerDeviceError::UnsupportedValue(_))
+            ));
         }
     }
+
+    #[test]
+    fn float_slider_bounds_and_precision() {
+        let setpoint = Param::new_float_slider("Setpoint", 16.0, 30.0, 0.5, 22.0);
+
+        assert_eq!(
+            setpoint.float_bounds(),
+            Some(&FloatParamBounds {
+                min: 16.0,
+                max: 30.0,
+                step: 0.5
+            })
+        );
+        assert_eq!(setpoint.bounds(), None);
+        assert_eq!(setpoint.precision(), Some(1));
+
+        let config = serde_json::to_value(&setpoint).unwrap();
+        assert_eq!(
+            config["bounds"],
+            json!({"min": 16.0, "max": 30.0, "step": 0.5})
+        );
+        assert_eq!(config["precision"], json!(1));
+        assert_eq!(config["data_type"], json!("float"));
+    }
+
+    #[test]
+    fn integer_and_float_bounds_replace_each_other() {
+        let mut param = Param::new_slider("Level", 0, 10, 1, 5);
+        assert!(param.float_bounds().is_none());
+
+        param.add_float_bounds(0.0, 1.0, 0.1);
+        assert!(param.bounds().is_none());
+        assert!(param.float_bounds().is_some());
+
+        param.add_bounds(0, 100, 5);
+        assert_eq!(
+            param.bounds(),
+            Some(&ParamBounds {
+                min: 0,
+                max: 100,
+                step: 5
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(&param).unwrap()["bounds"],
+            json!({"min": 0, "max": 100, "step": 5})
+        );
+    }
+
+    #[test]
+    fn step_precision_from_step() {
+        assert_eq!(step_precision(1.0), 0);
+        assert_eq!(step_precision(0.5), 1);
+        assert_eq!(step_precision(0.25), 2);
+        assert_eq!(step_precision(0.1), 1);
+    }
+
+    #[test]
+    fn bool_param_accepts_integers_and_booleans() {
+        let power = Param::new_power("Power", false);
+
+        assert_eq!(
+            power.value_from_json(&json!(1)),
+            Some(ParamValue::Bool(true))
+        );
+        assert_eq!(
+            power.value_from_json(&json!(0)),
+            Some(ParamValue::Bool(false))
+        );
+        assert_eq!(
+            power.value_from_json(&json!(true)),
+            Some(ParamValue::Bool(true))
+        );
+        assert_eq!(
+            power.value_from_json(&json!(false)),
+            Some(ParamValue::Bool(false))
+        );
+    }
+
+    #[test]
+    fn bool_param_rejects_other_values() {
+        let power = Param::new_power("Power", false);
+
+        assert_eq!(power.value_from_json(&json!(2)), None);
+        assert_eq!(power.value_from_json(&json!(1.0)), None);
+        assert_eq!(power.value_from_json(&json!("true")), None);
+    }
+
+    #[test]
+    fn bool_value_is_serialized_as_json_boolean() {
+        assert_eq!(Value::from(ParamValue::Bool(true)), json!(true));
+        assert_eq!(Value::from(ParamValue::Bool(false)), json!(false));
+    }
+
+    // tests run with the default strict number handling
+    #[test]
+    fn integer_param_accepts_integral_float() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(
+            level.value_from_json(&json!(50.0)),
+            Some(ParamValue::Integer(50))
+        );
+        assert_eq!(
+            level.value_from_json(&json!(-3.0)),
+            Some(ParamValue::Integer(-3))
+        );
+    }
+
+    #[test]
+    fn integer_param_rejects_fractional_float() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(level.value_from_json(&json!(50.7)), None);
+    }
+
+    #[test]
+    fn integer_param_rejects_out_of_range_integer() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(level.value_from_json(&json!(u64::MAX)), None);
+        assert_eq!(level.value_from_json(&json!(1e20)), None);
+        assert_eq!(level.value_from_json(&json!(i64::MAX as f64)), None);
+    }
+
+    #[test]
+    fn lenient_handling_rounds_floats() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(
+            level.value_from_json_with(&json!(50.7), NumberHandling::Lenient),
+            Some(ParamValue::Integer(51))
+        );
+        assert_eq!(
+            level.value_from_json_with(&json!(50.2), NumberHandling::Lenient),
+            Some(ParamValue::Integer(50))
+        );
+    }
+
+    #[test]
+    fn lenient_handling_clamps_to_bounds() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(
+            level.value_from_json_with(&json!(150), NumberHandling::Lenient),
+            Some(ParamValue::Integer(100))
+        );
+        assert_eq!(
+            level.value_from_json_with(&json!(-20.6), NumberHandling::Lenient),
+            Some(ParamValue::Integer(0))
+        );
+        assert_eq!(
+            level.value_from_json_with(&json!(1e20), NumberHandling::Lenient),
+            Some(ParamValue::Integer(100))
+        );
+    }
+
+    #[test]
+    fn lenient_handling_saturates_without_bounds() {
+        let counter = Param::new(
+            "Counter",
+            ParamValue::Integer(0),
+            ParamTypes::RangeController,
+            HashSet::from([ParamProperty::Read, ParamProperty::Write]),
+            ParamUi::Text,
+        );
+
+        assert_eq!(
+            counter.value_from_json_with(&json!(1e20), NumberHandling::Lenient),
+            Some(ParamValue::Integer(i64::MAX))
+        );
+        assert_eq!(
+            counter.value_from_json_with(&json!(-1e20), NumberHandling::Lenient),
+            Some(ParamValue::Integer(i64::MIN))
+        );
+    }
+
+    #[test]
+    fn strict_handling_does_not_clamp() {
+        let level = Param::new_slider("Level", 0, 100, 1, 0);
+
+        assert_eq!(
+            level.value_from_json_with(&json!(150), NumberHandling::Strict),
+            Some(ParamValue::Integer(150))
+        );
+    }
+
+    #[test]
+    fn float_param_accepts_integer() {
+        let temperature = Param::new_float_slider("Setpoint", 16.0, 30.0, 0.5, 22.0);
+
+        assert_eq!(
+            temperature.value_from_json(&json!(25)),
+            Some(ParamValue::Float(25.0))
+        );
+    }
 }
@@ -0,0 +1,270 @@
+//! A module for sensor devices which periodically sample and report readings.
+//!
+//! [TemperatureSensor] creates a standard temperature sensor device and reports values returned by a sampler at a fixed interval.
+//!
+//! [WifiDiagnostics] reports signal strength of the WiFi connection for monitoring health of the fleet.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    clock::{self, StopSignal},
+    device::{Device, DeviceType, ParamReporter},
+    error::RmakerDeviceError,
+    param::{Param, ParamValue},
+};
+
+/// Periodic sampler for a temperature sensor device.
+///
+/// Sampling is stopped when the instance is dropped.
+/// ```rust
+/// let device = TemperatureSensor::device("Temperature Sensor", 25.0)?;
+/// let sensor = TemperatureSensor::start(&device, Duration::from_secs(60), read_temperature)?;
+/// node.add_device(device)?;
+/// ```
+pub struct TemperatureSensor {
+    _sampler: PeriodicSampler,
+}
+
+impl TemperatureSensor {
+    pub const PARAM_NAME: &'static str = "Temperature";
+
+    /// Creates a temperature sensor device with `Temperature` as primary parameter.
+    pub fn device(name: &str, initial_value: f64) -> Result<Device, RmakerDeviceError> {
+        let mut device = Device::new(name, DeviceType::TemperatureSensor);
+        device.add_param(Param::new_temperature(Self::PARAM_NAME, initial_value))?;
+        device.set_primary_param(Self::PARAM_NAME)?;
+
+        Ok(device)
+    }
+
+    /// Starts sampling the `Temperature` parameter of the device at the provided interval.
+    ///
+    /// Readings are reported to RainMaker cloud, hence failures to report are logged if the agent is not started yet.
+    pub fn start(
+        device: &Device,
+        interval: Duration,
+        mut sampler: impl FnMut() -> f64 + Send + 'static,
+    ) -> Result<Self, RmakerDeviceError> {
+        let sampler = PeriodicSampler::start(device, Self::PARAM_NAME, interval, move || {
+            Some(ParamValue::Float(sampler()))
+        })?;
+
+        Ok(Self { _sampler: sampler })
+    }
+}
+
+/// Periodic reporter of WiFi signal strength(RSSI in dBm) as a diagnostic parameter.
+///
+/// On Linux, RSSI of the first wireless interface listed in `/proc/net/wireless` is reported.
+/// Nothing is reported while the signal strength is not available, e.g. when WiFi is not connected.
+/// ```rust
+/// let device = WifiDiagnostics::device("Diagnostics")?;
+/// let diagnostics = WifiDiagnostics::start(&device, Duration::from_secs(300))?;
+/// node.add_device(device)?;
+/// ```
+pub struct WifiDiagnostics {
+    _sampler: PeriodicSampler,
+}
+
+impl WifiDiagnostics {
+    pub const PARAM_NAME: &'static str = "RSSI";
+
+    /// Creates a generic sensor device with a read-only `RSSI` parameter.
+    pub fn device(name: &str) -> Result<Device, RmakerDeviceError> {
+        let mut device = Device::new(name, DeviceType::Sensor);
+        device.add_param(Param::new_rssi(Self::PARAM_NAME, wifi_rssi().unwrap_or(0)))?;
+
+        Ok(device)
+    }
+
+    /// Starts reporting the `RSSI` parameter of the device at the provided interval.
+    pub fn start(device: &Device, interval: Duration) -> Result<Self, RmakerDeviceError> {
+        let sampler = PeriodicSampler::start(device, Self::PARAM_NAME, interval, || {
+            wifi_rssi().map(|rssi| ParamValue::Integer(rssi as i64))
+        })?;
+
+        Ok(Self { _sampler: sampler })
+    }
+}
+
+/// Returns signal strength(RSSI in dBm) of the current WiFi connection, if available.
+#[cfg(target_os = "espidf")]
+pub fn wifi_rssi() -> Option<i32> {
+    use esp_idf_svc::sys::{esp_wifi_sta_get_ap_info, wifi_ap_record_t, ESP_OK};
+
+    let mut ap_info = wifi_ap_record_t::default();
+    match unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) } {
+        ESP_OK => Some(ap_info.rssi as i32),
+        _ => None,
+    }
+}
+
+/// Returns signal strength(RSSI in dBm) of the current WiFi connection, if available.
+#[cfg(target_os = "linux")]
+pub fn wifi_rssi() -> Option<i32> {
+    // Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+    //  face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+    // wlan0: 0000   70.  -40.  -256        0      0      0      0      0        0
+    let wireless = std::fs::read_to_string("/proc/net/wireless").ok()?;
+    let level = wireless.lines().nth(2)?.split_whitespace().nth(3)?;
+
+    level.trim_end_matches('.').parse().ok()
+}
+
+// reports values returned by sampler till dropped. samples which are None are skipped
+struct PeriodicSampler {
+    stop: Arc<StopSignal>,
+}
+
+// scheduled on the clock once per interval till sampler is dropped
+struct Sample {
+    reporter: ParamReporter,
+    param_name: &'static str,
+    interval: Duration,
+    sampler: Box<dyn FnMut() -> Option<ParamValue> + Send>,
+    stop: Arc<StopSignal>,
+}
+
+impl PeriodicSampler {
+    fn start(
+        device: &Device,
+        param_name: &'static str,
+        interval: Duration,
+        sampler: impl FnMut() -> Option<ParamValue> + Send + 'static,
+    ) -> Result<Self, RmakerDeviceError> {
+        if device.param(param_name).is_none() {
+            return Err(RmakerDeviceError::ParamNotFound(param_name.to_string()));
+        }
+
+        let stop = Arc::new(StopSignal::new());
+        Sample {
+            reporter: device.reporter(),
+            param_name,
+            interval,
+            sampler: Box::new(sampler),
+            stop: stop.clone(),
+        }
+        .schedule();
+
+        Ok(Self { stop })
+    }
+}
+
+impl Sample {
+    fn schedule(mut self) {
+        clock::run_after(self.interval, move || {
+            if self.stop.is_stopped() {
+                return;
+            }
+
+            if let Some(value) = (self.sampler)() {
+                if self.reporter.report_param(self.param_name, value).is_err() {
+                    log::error!("could not report {}", self.param_name);
+                }
+            }
+            self.schedule();
+        });
+    }
+}
+
+impl Drop for PeriodicSampler {
+    fn drop(&mut self) {
+        // sample which is already scheduled is skipped
+        self.stop.stop();
+    }
+}
+
+#[cfg(all(test, feature = "mock-mqtt", feature = "mock-clock"))]
+mod tests {
+    use super::*;
+    use crate::{encoding, rmaker_mqtt};
+    use serde_json::{json, Value};
+
+    // params reported since the last call
+    fn reported_params() -> Vec<Value> {
+        rmaker_mqtt::mock::take_published()
+            .into_iter()
+            .filter(|msg| msg.topic == "node/node-1/params/local")
+            .filter_map(|msg| encoding::decode(&msg.payload))
+            .collect()
+    }
+
+    #[test]
+    fn temperature_is_sampled_and_reported_at_interval() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let device = TemperatureSensor::device("Thermometer", 25.0).unwrap();
+        device.set_node_id("node-1");
+        let mut readings = vec![21.0, 22.5].into_iter();
+        let _sensor = TemperatureSensor::start(&device, Duration::from_secs(60), move || {
+            readings.next().unwrap()
+        })
+        .unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert!(reported_params().is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            reported_params(),
+            vec![json!({"Thermometer": {"Temperature": 21.0}})]
+        );
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(
+            reported_params(),
+            vec![json!({"Thermometer": {"Temperature": 22.5}})]
+        );
+    }
+
+    #[test]
+    fn sampling_stops_once_dropped() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let device = TemperatureSensor::device("Thermometer", 25.0).unwrap();
+        device.set_node_id("node-1");
+        let sensor = TemperatureSensor::start(&device, Duration::from_secs(60), || 22.5).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reported_params().len(), 1);
+
+        drop(sensor);
+        clock.advance(Duration::from_secs(120));
+        assert!(reported_params().is_empty());
+    }
+
+    #[test]
+    fn wifi_diagnostics_registers_read_only_rssi_param() {
+        let device = WifiDiagnostics::device("Diagnostics").unwrap();
+
+        let rssi = device.param(WifiDiagnostics::PARAM_NAME).unwrap();
+        assert_eq!(
+            serde_json::to_value(rssi).unwrap()["type"],
+            "esp.param.rssi"
+        );
+        assert!(!rssi.is_writable());
+    }
+
+    // signal strength of sandboxed hosts is not known, hence it is sampled from a stub
+    #[test]
+    fn sampled_rssi_is_reported() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+        let clock = clock::mock();
+        let device = WifiDiagnostics::device("Diagnostics").unwrap();
+        device.set_node_id("node-1");
+        let _sampler = PeriodicSampler::start(
+            &device,
+            WifiDiagnostics::PARAM_NAME,
+            Duration::from_secs(300),
+            || Some(ParamValue::Integer(-52)),
+        )
+        .unwrap();
+
+        clock.advance(Duration::from_secs(300));
+
+        assert_eq!(
+            reported_params(),
+            vec![json!({"Diagnostics": {"RSSI": -52}})]
+        );
+    }
+}
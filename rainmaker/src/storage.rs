@@ -0,0 +1,55 @@
+// runtime state of the agent, e.g. hash of the last published node configuration.
+// stored in a namespace of the runtime NVS partition provided by application, factory partition only holds
+// node credentials. state is not persisted if no partition is provided
+use std::sync::OnceLock;
+
+use rainmaker_components::persistent_storage::{Nvs, NvsPartition};
+
+use crate::error::RmakerStorageError;
+
+const NAMESPACE: &str = "rmaker_state";
+// hash of the last published node configuration
+const CONFIG_HASH_KEY: &str = "config_hash";
+
+static PARTITION: OnceLock<NvsPartition> = OnceLock::new();
+
+pub(crate) fn init(partition: NvsPartition) -> Result<(), RmakerStorageError> {
+    PARTITION
+        .set(partition)
+        .map_err(|_| RmakerStorageError::AlreadyInitialized)
+}
+
+fn get_nvs() -> Result<Nvs, RmakerStorageError> {
+    let partition = PARTITION.get().ok_or(RmakerStorageError::NotInitialized)?;
+
+    Nvs::new(partition.clone(), NAMESPACE).map_err(|_| RmakerStorageError::NamespaceNotFound)
+}
+
+// returns None if hash is not stored yet or partition is not available
+pub(crate) fn get_config_hash() -> Option<u64> {
+    let nvs = get_nvs().ok()?;
+    let mut buff = [0; 16];
+    let bytes = nvs.get_bytes(CONFIG_HASH_KEY, &mut buff).ok()??;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+pub(crate) fn set_config_hash(hash: u64) -> Result<(), RmakerStorageError> {
+    let mut nvs = get_nvs()?;
+    nvs.set_bytes(CONFIG_HASH_KEY, &hash.to_le_bytes())
+        .map_err(|_| RmakerStorageError::ValueWriteError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // partition is never provided in unit tests
+    #[test]
+    fn state_is_not_persisted_without_partition() {
+        assert!(matches!(
+            set_config_hash(42),
+            Err(RmakerStorageError::NotInitialized)
+        ));
+        assert_eq!(get_config_hash(), None);
+    }
+}
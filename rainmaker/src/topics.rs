@@ -0,0 +1,90 @@
+// MQTT topics of the node, i.e. node/<node_id>/<suffix>. suffixes can be overridden for compatible backends
+use std::sync::OnceLock;
+
+use crate::{constants::*, error::RmakerError};
+
+static TOPIC_CONFIG: OnceLock<TopicConfig> = OnceLock::new();
+
+// the global config can be set only once per process, so tests override it per thread instead
+#[cfg(test)]
+thread_local! {
+    static TEST_TOPIC_CONFIG: std::cell::Cell<Option<&'static TopicConfig>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Suffixes of MQTT topics used by the agent, for RainMaker-compatible backends with a different topic scheme.
+///
+/// Topics are built as `node/<node_id>/<suffix>`. Defaults to the RainMaker topic scheme.
+/// ```rust
+/// rmaker.set_topic_config(TopicConfig {
+///     params_remote: "params/set".to_string(),
+///     ..Default::default()
+/// })?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TopicConfig {
+    /// Node configuration, `config` by default.
+    pub config: String,
+    /// Partial updates of node configuration, `config/patch` by default.
+    pub config_patch: String,
+    /// Initial values of params, `params/local/init` by default.
+    pub params_local_init: String,
+    /// Reported values of params, `params/local` by default.
+    pub params_local: String,
+    /// Values of params received from cloud, `params/remote` by default.
+    pub params_remote: String,
+    /// Requests for reporting current values of params, `params/get` by default.
+    pub params_get: String,
+    /// Heartbeat, `heartbeat` by default.
+    pub heartbeat: String,
+    /// Alerts, `alert` by default.
+    pub alert: String,
+    /// User-node mapping, `user/mapping` by default.
+    pub user_mapping: String,
+    /// Acknowledgments of commands, `cmd_resp` by default.
+    pub cmd_resp: String,
+    /// Time series data, i.e. values reported with a timestamp, `tsdata` by default.
+    pub ts_data: String,
+}
+
+impl Default for TopicConfig {
+    fn default() -> Self {
+        Self {
+            config: NODE_CONFIG_TOPIC_SUFFIX.to_string(),
+            config_patch: NODE_CONFIG_PATCH_TOPIC_SUFFIX.to_string(),
+            params_local_init: NODE_PARAMS_LOCAL_INIT_TOPIC_SUFFIX.to_string(),
+            params_local: NODE_PARAMS_LOCAL_TOPIC_SUFFIX.to_string(),
+            params_remote: NODE_PARAMS_REMOTE_TOPIC_SUFFIX.to_string(),
+            params_get: NODE_PARAMS_GET_TOPIC_SUFFIX.to_string(),
+            heartbeat: NODE_HEARTBEAT_TOPIC_SUFFIX.to_string(),
+            alert: NODE_ALERT_TOPIC_SUFFIX.to_string(),
+            user_mapping: USER_MAPPING_TOPIC_SUFFIX.to_string(),
+            cmd_resp: NODE_CMD_RESP_TOPIC_SUFFIX.to_string(),
+            ts_data: NODE_TS_DATA_TOPIC_SUFFIX.to_string(),
+        }
+    }
+}
+
+// fails if any topic is built already, since topics are cached by devices and subscriptions
+pub(crate) fn set_topic_config(config: TopicConfig) -> Result<(), RmakerError> {
+    TOPIC_CONFIG
+        .set(config)
+        .map_err(|_| RmakerError::AlreadyInitialized)
+}
+
+pub(crate) fn config() -> &'static TopicConfig {
+    #[cfg(test)]
+    if let Some(config) = TEST_TOPIC_CONFIG.get() {
+        return config;
+    }
+    TOPIC_CONFIG.get_or_init(TopicConfig::default)
+}
+
+#[cfg(test)]
+pub(crate) fn set_test_topic_config(config: TopicConfig) {
+    TEST_TOPIC_CONFIG.set(Some(Box::leak(Box::new(config))));
+}
+
+pub(crate) fn node_topic(node_id: &str, suffix: &str) -> String {
+    format!("node/{}/{}", node_id, suffix)
+}
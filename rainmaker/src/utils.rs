@@ -1,9 +1,203 @@
 // utility types and functions for using throughout the crate
+use serde::{ser::SerializeSeq, Serializer};
+use serde_json::{json, Value};
 use std::sync::{Arc, Mutex};
 
 pub(crate) type WrappedInArcMutex<T> = Arc<Mutex<T>>;
 
-#[allow(dead_code)]
 pub(crate) fn wrap_in_arc_mutex<T>(inp: T) -> WrappedInArcMutex<T> {
     Arc::new(Mutex::new(inp))
 }
+
+// attributes in insertion order. setting an existing attribute replaces its value in place
+pub(crate) type Attributes = Vec<(String, String)>;
+
+pub(crate) fn set_attribute(attributes: &mut Attributes, name: String, value: String) {
+    match attributes.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, v)) => *v = value,
+        None => attributes.push((name, value)),
+    }
+}
+
+// serializes attributes as array of {"name": .., "value": ..} objects, as expected in node configuration
+pub(crate) fn serialize_attributes<S>(
+    attributes: &Attributes,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(attributes.len()))?;
+    for (name, value) in attributes {
+        seq.serialize_element(&serde_json::json!({ "name": name, "value": value }))?;
+    }
+    seq.end()
+}
+
+// 64-bit FNV-1a hash. unlike std hashers, output is stable across builds and platforms
+pub(crate) fn fnv1a_hash(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    data.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+// JSON Patch(RFC 6902) operations transforming old into new. arrays of different length are replaced as a whole
+pub(crate) fn json_patch(old: &Value, new: &Value) -> Vec<Value> {
+    let mut ops = Vec::new();
+    diff_values("", old, new, &mut ops);
+    ops
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, ops: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let key_path = format!("{}/{}", path, escape_pointer(key));
+                match new.get(key) {
+                    Some(new_value) => diff_values(&key_path, old_value, new_value, ops),
+                    None => ops.push(json!({ "op": "remove", "path": key_path })),
+                }
+            }
+            for (key, new_value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                let key_path = format!("{}/{}", path, escape_pointer(key));
+                ops.push(json!({ "op": "add", "path": key_path, "value": new_value }));
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items))
+            if old_items.len() == new_items.len() =>
+        {
+            for (index, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                diff_values(&format!("{}/{}", path, index), old_item, new_item, ops);
+            }
+        }
+        _ if old != new => ops.push(json!({ "op": "replace", "path": path, "value": new })),
+        _ => {}
+    }
+}
+
+// escapes reference token of JSON pointer(RFC 6901)
+fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+// PEM encoding of DER data with the provided label, e.g. CERTIFICATE
+#[cfg(all(target_os = "linux", feature = "pkcs12"))]
+pub(crate) fn pem_encode(label: &str, der: &[u8]) -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    // base64 output is ascii, hence chunks are valid utf-8
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_are_serialized_in_insertion_order() {
+        let mut attributes = Attributes::new();
+        for (name, value) in [("serial", "1234"), ("model", "EX-100"), ("batch", "7")] {
+            set_attribute(&mut attributes, name.to_string(), value.to_string());
+        }
+        // replaced value keeps its position
+        set_attribute(&mut attributes, "model".to_string(), "EX-200".to_string());
+
+        assert_eq!(
+            serialize_attributes(&attributes, serde_json::value::Serializer).unwrap(),
+            json!([
+                {"name": "serial", "value": "1234"},
+                {"name": "model", "value": "EX-200"},
+                {"name": "batch", "value": "7"}
+            ])
+        );
+    }
+
+    #[test]
+    fn json_patch_of_identical_values_is_empty() {
+        let value = json!({"name": "Light", "params": [{"name": "Power"}]});
+
+        assert!(json_patch(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn json_patch_replaces_changed_leaf() {
+        let old =
+            json!({"devices": [{"name": "Light", "params": [{"bounds": {"min": 0, "max": 100}}]}]});
+        let new =
+            json!({"devices": [{"name": "Light", "params": [{"bounds": {"min": 0, "max": 50}}]}]});
+
+        assert_eq!(
+            json_patch(&old, &new),
+            vec![json!({"op": "replace", "path": "/devices/0/params/0/bounds/max", "value": 50})]
+        );
+    }
+
+    #[test]
+    fn json_patch_adds_and_removes_keys() {
+        let old = json!({"info": {"name": "Node", "model": "EX-100"}});
+        let new = json!({"info": {"name": "Node", "platform": "esp32"}});
+
+        assert_eq!(
+            json_patch(&old, &new),
+            vec![
+                json!({"op": "remove", "path": "/info/model"}),
+                json!({"op": "add", "path": "/info/platform", "value": "esp32"})
+            ]
+        );
+    }
+
+    #[test]
+    fn json_patch_replaces_resized_array() {
+        let old = json!({"devices": [{"name": "Light"}]});
+        let new = json!({"devices": [{"name": "Light"}, {"name": "Fan"}]});
+
+        assert_eq!(
+            json_patch(&old, &new),
+            vec![
+                json!({"op": "replace", "path": "/devices", "value": [{"name": "Light"}, {"name": "Fan"}]})
+            ]
+        );
+    }
+
+    #[test]
+    fn json_patch_escapes_pointer_tokens() {
+        let old = json!({"a/b": 1, "c~d": 1});
+        let new = json!({"a/b": 2, "c~d": 2});
+
+        assert_eq!(
+            json_patch(&old, &new),
+            vec![
+                json!({"op": "replace", "path": "/a~1b", "value": 2}),
+                json!({"op": "replace", "path": "/c~0d", "value": 2})
+            ]
+        );
+    }
+
+    #[cfg(all(target_os = "linux", feature = "pkcs12"))]
+    #[test]
+    fn pem_lines_are_wrapped_at_64_characters() {
+        let der: Vec<u8> = (0..100).collect();
+
+        assert_eq!(
+            pem_encode("CERTIFICATE", &der),
+            concat!(
+                "-----BEGIN CERTIFICATE-----\n",
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4v\n",
+                "MDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5f\n",
+                "YGFiYw==\n",
+                "-----END CERTIFICATE-----\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! WiFi access point scanning, e.g. for offering networks to the user during provisioning.
+//!
+//! Scan results can be served to phone apps over a provisioning endpoint registered using
+//! [`reg_wifi_scan_ep`](crate::Rainmaker::reg_wifi_scan_ep).
+
+use serde_json::{json, Value};
+
+use crate::error::RmakerError;
+
+/// Name of the provisioning endpoint serving scan results.
+pub const WIFI_SCAN_ENDPOINT: &str = "rmaker-wifi-scan";
+
+/// Access point found by a WiFi scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApInfo {
+    pub ssid: String,
+    pub bssid: [u8; 6],
+    /// Signal strength in dBm.
+    pub rssi: i32,
+    pub channel: u8,
+    /// Whether the access point requires no authentication.
+    pub open: bool,
+}
+
+impl ApInfo {
+    fn to_json(&self) -> Value {
+        let bssid: Vec<String> = self.bssid.iter().map(|b| format!("{:02x}", b)).collect();
+        json!({
+            "ssid": self.ssid,
+            "bssid": bssid.join(":"),
+            "rssi": self.rssi,
+            "channel": self.channel,
+            "open": self.open
+        })
+    }
+}
+
+/// Scans for nearby WiFi access points, sorted by signal strength.
+///
+/// Blocks till the scan is completed. Returns [`RmakerError::UnknownError`] if the scan could not be performed.
+#[cfg(target_os = "espidf")]
+pub fn scan() -> Result<Vec<ApInfo>, RmakerError> {
+    use esp_idf_svc::sys::{
+        esp_wifi_scan_get_ap_num, esp_wifi_scan_get_ap_records, esp_wifi_scan_start,
+        wifi_ap_record_t, wifi_auth_mode_t_WIFI_AUTH_OPEN, ESP_OK,
+    };
+
+    let mut ap_count: u16 = 0;
+    if unsafe { esp_wifi_scan_start(std::ptr::null(), true) } != ESP_OK
+        || unsafe { esp_wifi_scan_get_ap_num(&mut ap_count) } != ESP_OK
+    {
+        log::error!("could not scan wifi");
+        return Err(RmakerError::UnknownError);
+    }
+
+    let mut records = vec![wifi_ap_record_t::default(); ap_count as usize];
+    if unsafe { esp_wifi_scan_get_ap_records(&mut ap_count, records.as_mut_ptr()) } != ESP_OK {
+        log::error!("could not get wifi scan results");
+        return Err(RmakerError::UnknownError);
+    }
+    records.truncate(ap_count as usize);
+
+    let mut aps: Vec<ApInfo> = records
+        .iter()
+        .map(|record| {
+            // ssid is null terminated
+            let ssid_len = record
+                .ssid
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap_or(record.ssid.len());
+            ApInfo {
+                ssid: String::from_utf8_lossy(&record.ssid[..ssid_len]).into_owned(),
+                bssid: record.bssid,
+                rssi: record.rssi as i32,
+                channel: record.primary,
+                open: record.authmode == wifi_auth_mode_t_WIFI_AUTH_OPEN,
+            }
+        })
+        .collect();
+    aps.sort_by(|a, b| b.rssi.cmp(&a.rssi));
+
+    Ok(aps)
+}
+
+/// Scans for nearby WiFi access points, sorted by signal strength.
+///
+/// Network connectivity is managed by the host on Linux, hence returns [`RmakerError::Unsupported`].
+#[cfg(target_os = "linux")]
+pub fn scan() -> Result<Vec<ApInfo>, RmakerError> {
+    Err(RmakerError::Unsupported)
+}
+
+// response of scan endpoint, list of access points or the error
+pub(crate) fn scan_response() -> Vec<u8> {
+    encode_scan_result(scan())
+}
+
+fn encode_scan_result(result: Result<Vec<ApInfo>, RmakerError>) -> Vec<u8> {
+    let response = match result {
+        Ok(aps) => json!({
+            "status": "success",
+            "aps": aps.iter().map(ApInfo::to_json).collect::<Vec<_>>()
+        }),
+        Err(RmakerError::Unsupported) => json!({"status": "unsupported"}),
+        Err(_) => json!({"status": "failure"}),
+    };
+
+    response.to_string().into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_list_is_encoded_for_endpoint() {
+        let aps = vec![
+            ApInfo {
+                ssid: "home".to_string(),
+                bssid: [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff],
+                rssi: -40,
+                channel: 6,
+                open: false,
+            },
+            ApInfo {
+                ssid: "guest".to_string(),
+                bssid: [0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+                rssi: -72,
+                channel: 11,
+                open: true,
+            },
+        ];
+
+        let response: Value = serde_json::from_slice(&encode_scan_result(Ok(aps))).unwrap();
+
+        assert_eq!(
+            response,
+            json!({
+                "status": "success",
+                "aps": [
+                    {"ssid": "home", "bssid": "aa:bb:cc:dd:ee:ff", "rssi": -40, "channel": 6, "open": false},
+                    {"ssid": "guest", "bssid": "01:02:03:04:05:06", "rssi": -72, "channel": 11, "open": true}
+                ]
+            })
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scan_is_unsupported_on_linux() {
+        assert!(matches!(scan(), Err(RmakerError::Unsupported)));
+
+        let response: Value = serde_json::from_slice(&scan_response()).unwrap();
+        assert_eq!(response, json!({"status": "unsupported"}));
+    }
+}
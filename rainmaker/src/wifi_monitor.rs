@@ -0,0 +1,134 @@
+// pauses publishing while WiFi is disconnected and triggers reconnection
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    clock::{self, StopSignal},
+    rmaker_mqtt,
+};
+
+pub(crate) struct WifiMonitor {
+    stop: Arc<StopSignal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WifiMonitor {
+    pub(crate) fn start(interval: Duration) -> Self {
+        let stop = Arc::new(StopSignal::new());
+        let stop_2 = stop.clone();
+
+        // runs till monitor is dropped
+        let handle = thread::spawn(move || {
+            let mut connected = true;
+            while clock::wait(&stop_2, interval) {
+                let now_connected = is_wifi_connected();
+                handle_wifi_state(connected, now_connected);
+                connected = now_connected;
+            }
+            rmaker_mqtt::set_paused(false);
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for WifiMonitor {
+    fn drop(&mut self) {
+        self.stop.stop();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!("wifi monitor thread panicked");
+            }
+        }
+    }
+}
+
+// pauses publishing once WiFi is lost, resumes once it is back and retries while it is down
+fn handle_wifi_state(was_connected: bool, connected: bool) {
+    if was_connected && !connected {
+        log::warn!("wifi disconnected. pausing publishes");
+        rmaker_mqtt::set_paused(true);
+    } else if !was_connected && connected {
+        log::info!("wifi connected. resuming publishes");
+        rmaker_mqtt::set_paused(false);
+    }
+    if !connected {
+        reconnect_wifi();
+    }
+}
+
+// returns false if WiFi is not connected within the timeout
+pub(crate) fn wait_for_wifi(timeout: Duration) -> bool {
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    let deadline = clock::now() + timeout;
+    while !is_wifi_connected() {
+        let now = clock::now();
+        if now >= deadline {
+            return false;
+        }
+        clock::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+
+    true
+}
+
+#[cfg(target_os = "espidf")]
+fn is_wifi_connected() -> bool {
+    crate::sensor::wifi_rssi().is_some()
+}
+
+#[cfg(target_os = "espidf")]
+fn reconnect_wifi() {
+    if unsafe { esp_idf_svc::sys::esp_wifi_connect() } != esp_idf_svc::sys::ESP_OK {
+        log::error!("could not trigger wifi reconnection");
+    }
+}
+
+// network connectivity is managed by the host on linux
+#[cfg(target_os = "linux")]
+fn is_wifi_connected() -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+fn reconnect_wifi() {}
+
+#[cfg(all(test, feature = "mock-mqtt"))]
+mod tests {
+    use super::*;
+
+    fn publish_power(power: bool) {
+        let payload = format!(r#"{{"Light": {{"Power": {}}}}}"#, power);
+        rmaker_mqtt::publish("node/node-1/params/local", payload.into_bytes()).unwrap();
+    }
+
+    #[test]
+    fn publishes_are_held_while_wifi_is_disconnected() {
+        let _mqtt = rmaker_mqtt::mock::lock();
+
+        handle_wifi_state(true, false);
+        publish_power(true);
+        publish_power(false);
+        assert!(rmaker_mqtt::mock::take_published().is_empty());
+
+        // still disconnected
+        handle_wifi_state(false, false);
+        assert!(rmaker_mqtt::mock::take_published().is_empty());
+
+        // only the latest message of a topic is kept while held
+        handle_wifi_state(false, true);
+        let published = rmaker_mqtt::mock::take_published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].payload, br#"{"Light": {"Power": false}}"#);
+
+        publish_power(true);
+        assert_eq!(rmaker_mqtt::mock::take_published().len(), 1);
+    }
+}